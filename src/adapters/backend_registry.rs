@@ -0,0 +1,195 @@
+use std::env;
+use std::io::IsTerminal;
+
+use anyhow::Result;
+use log::info;
+
+use crate::adapters::local_adapter::LocalAdapter;
+use crate::adapters::terminal_adapter::TerminalAdapter;
+use crate::adapters::tmux_adapter::TmuxAdapter;
+use crate::domain::models::BackendKind;
+use crate::ports::{FileSystemPort, TerminalPort};
+
+/// Environment variable overriding the detected backend
+pub const BACKEND_ENV: &str = "WZB_BACKEND";
+
+/// Facts about the running environment used to pick a backend
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    pub wezterm_pane: Option<String>,
+    pub tmux: Option<String>,
+    pub is_tty: bool,
+    pub wezterm_binary: Option<String>,
+}
+
+impl Environment {
+    pub fn detect<F: FileSystemPort>(file_system: &F) -> Self {
+        Self {
+            wezterm_pane: non_empty_var("WEZTERM_PANE"),
+            tmux: non_empty_var("TMUX"),
+            is_tty: std::io::stdout().is_terminal(),
+            wezterm_binary: file_system.find_executable("wezterm"),
+        }
+    }
+}
+
+fn non_empty_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// The chosen backend and a human readable explanation of the choice
+#[derive(Debug, Clone)]
+pub struct BackendSelection {
+    pub kind: BackendKind,
+    pub reason: String,
+    pub environment: Environment,
+}
+
+/// Overrides in order of precedence: CLI flag, environment, config file
+#[derive(Debug, Clone, Default)]
+pub struct BackendOverrides {
+    pub flag: Option<BackendKind>,
+    pub env: Option<String>,
+    pub config: Option<BackendKind>,
+}
+
+pub struct BackendRegistry;
+
+impl BackendRegistry {
+    pub fn select(
+        overrides: BackendOverrides,
+        environment: Environment,
+    ) -> Result<BackendSelection> {
+        let (kind, reason) = if let Some(kind) = overrides.flag {
+            (kind, "selected with the --backend flag".to_string())
+        } else if let Some(value) = overrides.env {
+            (
+                value.parse::<BackendKind>()?,
+                format!("selected with {}={}", BACKEND_ENV, value),
+            )
+        } else if let Some(kind) = overrides.config {
+            (kind, "selected by settings.backend in config".to_string())
+        } else {
+            Self::detect(&environment)
+        };
+
+        info!("Backend: {} ({})", kind, reason);
+        Ok(BackendSelection {
+            kind,
+            reason,
+            environment,
+        })
+    }
+
+    fn detect(environment: &Environment) -> (BackendKind, String) {
+        match environment {
+            Environment {
+                wezterm_pane: Some(pane),
+                wezterm_binary: Some(_),
+                ..
+            } => (
+                BackendKind::WezTerm,
+                format!("running inside WezTerm pane {}", pane),
+            ),
+            Environment { tmux: Some(_), .. } => (
+                BackendKind::Tmux,
+                "running inside a tmux session".to_string(),
+            ),
+            Environment { is_tty: true, .. } => (
+                BackendKind::Local,
+                "no multiplexer detected, running in the current terminal".to_string(),
+            ),
+            Environment {
+                wezterm_binary: Some(path),
+                ..
+            } => (
+                BackendKind::WezTerm,
+                format!("no terminal attached, using the wezterm mux at {}", path),
+            ),
+            _ => (
+                BackendKind::Local,
+                "no terminal or multiplexer detected".to_string(),
+            ),
+        }
+    }
+
+    pub fn create(kind: BackendKind) -> Box<dyn TerminalPort> {
+        match kind {
+            BackendKind::WezTerm => Box::new(TerminalAdapter::new()),
+            BackendKind::Tmux => Box::new(TmuxAdapter::new()),
+            BackendKind::Local => Box::new(LocalAdapter::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wezterm_environment() -> Environment {
+        Environment {
+            wezterm_pane: Some("3".to_string()),
+            tmux: None,
+            is_tty: true,
+            wezterm_binary: Some("/usr/bin/wezterm".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_detects_wezterm_pane() {
+        let selection =
+            BackendRegistry::select(BackendOverrides::default(), wezterm_environment()).unwrap();
+
+        assert_eq!(selection.kind, BackendKind::WezTerm);
+        assert!(selection.reason.contains("pane 3"));
+    }
+
+    #[test]
+    fn test_detects_tmux_before_plain_tty() {
+        let environment = Environment {
+            tmux: Some("/tmp/tmux-1000/default,1,0".to_string()),
+            is_tty: true,
+            ..Default::default()
+        };
+
+        let selection = BackendRegistry::select(BackendOverrides::default(), environment).unwrap();
+        assert_eq!(selection.kind, BackendKind::Tmux);
+    }
+
+    #[test]
+    fn test_falls_back_to_local() {
+        let selection =
+            BackendRegistry::select(BackendOverrides::default(), Environment::default()).unwrap();
+        assert_eq!(selection.kind, BackendKind::Local);
+    }
+
+    #[test]
+    fn test_override_precedence() {
+        let overrides = BackendOverrides {
+            flag: None,
+            env: Some("tmux".to_string()),
+            config: Some(BackendKind::Local),
+        };
+        let selection = BackendRegistry::select(overrides, wezterm_environment()).unwrap();
+        assert_eq!(selection.kind, BackendKind::Tmux);
+        assert!(selection.reason.contains(BACKEND_ENV));
+
+        let overrides = BackendOverrides {
+            flag: Some(BackendKind::Local),
+            env: Some("tmux".to_string()),
+            config: None,
+        };
+        let selection = BackendRegistry::select(overrides, wezterm_environment()).unwrap();
+        assert_eq!(selection.kind, BackendKind::Local);
+    }
+
+    #[test]
+    fn test_invalid_env_override() {
+        let overrides = BackendOverrides {
+            env: Some("kitty".to_string()),
+            ..Default::default()
+        };
+        let result = BackendRegistry::select(overrides, wezterm_environment());
+        assert!(result.is_err());
+    }
+}
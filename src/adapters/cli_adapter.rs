@@ -1,30 +1,69 @@
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
 use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
 use std::process::ExitStatus;
+use std::time::Duration;
 
 use anyhow::Result;
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use log::info;
 
-use crate::domain::behaviours::TaskExecutionService;
-use crate::domain::models::{Direction, TaskClose};
-use crate::ports::{ConfigPort, TerminalPort};
+use crate::adapters::backend_registry::BackendSelection;
+use crate::adapters::completions::{completion_script, task_names, CompletionShell};
+use crate::adapters::wezterm_export::{keys_module, launch_menu, palette, ExportFormat, KeyAction};
+use crate::domain::behaviours::{
+    history_list, in_namespace, resolve_task_name, run_summary, statusline, task_list, unix_now,
+    DiagnosticsService, TaskExecutionService,
+};
+use crate::domain::models::{
+    parse_duration, BackendKind, Check, CheckStatus, ConfigFormat, Direction, DomainError,
+    FailureKind, ProjectConfig, Task, TaskClose, NAMESPACE_SEPARATOR,
+};
+use crate::domain::params::resolve_params;
+use crate::domain::picker::{picker_items, Picker, Selection};
+use crate::domain::variables::VariableService;
+use crate::ports::{
+    ConfigPort, DiagnosticsPort, EnvironmentPort, PromptPort, StatePort, TerminalPort,
+};
 
 /// Application CLI command structure
 #[derive(Parser)]
 #[command(name = "Wez Bits")]
-#[command(version = "0.8.0")]
+#[command(version = crate::constants::VERSION)]
 #[command(about = crate::constants::BANNER, long_about = None)]
 pub struct Cli {
     #[command(subcommand)]
     cmd: CliSubCmd,
+
+    /// Terminal backend hosting task panes (detected when omitted)
+    #[arg(short, long, global = true)]
+    backend: Option<BackendOption>,
+
+    /// Run as if wez bits was started in this directory
+    #[arg(short = 'C', long, global = true, value_name = "DIR")]
+    directory: Option<PathBuf>,
+}
+
+impl Cli {
+    /// Backend requested on the command line, if any
+    pub fn backend(&self) -> Option<BackendKind> {
+        self.backend.as_ref().map(BackendOption::to_backend_kind)
+    }
+
+    /// Directory to resolve the project from, if given
+    pub fn directory(&self) -> Option<&Path> {
+        self.directory.as_deref()
+    }
 }
 
 #[derive(Debug, Subcommand)]
 enum CliSubCmd {
     /// Run a project scoped task
     TaskRunner {
-        /// Task name in config file
-        name: String,
+        /// Task names in config file, run in order and picked interactively when
+        /// omitted. A namespace such as `rust:` lists its tasks.
+        names: Vec<String>,
 
         /// Configure when should a task pane close.
         #[arg(short, long, default_value = "on-success")]
@@ -33,6 +72,32 @@ enum CliSubCmd {
         /// Direction to open the panel
         #[arg(short, long, default_value = "right")]
         direction: TaskDirectionOption,
+
+        /// Value for one of the task's params, asked for when omitted
+        #[arg(short, long = "param", value_name = "NAME=VALUE", value_parser = parse_param)]
+        params: Vec<(String, String)>,
+
+        /// Run the remaining tasks after one fails
+        #[arg(short, long)]
+        keep_going: bool,
+
+        /// Run up to N tasks at once
+        #[arg(short = 'j', long, value_name = "N", default_value = "1")]
+        parallel: NonZeroUsize,
+
+        /// Interrupt tasks still running after this long, e.g. `10m`, overriding their `timeout`
+        #[arg(long, value_name = "DURATION", value_parser = parse_timeout)]
+        timeout: Option<Duration>,
+
+        /// Arguments appended to the task's, or filling its `{args}` and `{1}` placeholders
+        #[arg(last = true, value_name = "ARGS")]
+        extra_args: Vec<String>,
+    },
+
+    /// Inspect the project's tasks
+    Task {
+        #[command(subcommand)]
+        cmd: TaskSubCmd,
     },
 
     /// Interact with wez bits configuration
@@ -40,6 +105,158 @@ enum CliSubCmd {
         #[command(subcommand)]
         cmd: ConfigSubCmd,
     },
+
+    /// Diagnose the environment wez bits runs in
+    Doctor {},
+
+    /// Print a compact summary of running and failed tasks
+    Statusline {},
+
+    /// Show the tasks run in this project, oldest first
+    History {
+        /// Only runs of this task
+        task: Option<String>,
+
+        /// Only runs that failed
+        #[arg(short, long)]
+        failed: bool,
+
+        /// Show the last N runs
+        #[arg(short = 'n', long, value_name = "N", default_value = "20")]
+        limit: usize,
+
+        /// Print the runs as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Run the last task again with the same arguments, params and pane settings
+    Again {},
+
+    /// Generate wezterm.lua snippets from the config
+    Wezterm {
+        #[command(subcommand)]
+        cmd: WeztermSubCmd,
+    },
+
+    /// Print a shell completion script
+    Completions {
+        /// Shell to complete for
+        shell: ShellOption,
+    },
+
+    /// Values for the shell completion scripts
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        #[command(subcommand)]
+        cmd: CompleteSubCmd,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum TaskSubCmd {
+    /// List tasks by group with their descriptions
+    List {
+        /// Include hidden and `_` prefixed helper tasks
+        #[arg(short, long)]
+        all: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum CompleteSubCmd {
+    /// Task and register names from the nearest config
+    Tasks {},
+}
+
+#[derive(ValueEnum, Debug, Clone)]
+enum ShellOption {
+    Bash,
+    Zsh,
+    Fish,
+    Nushell,
+}
+
+impl ShellOption {
+    fn to_completion_shell(&self) -> CompletionShell {
+        match self {
+            ShellOption::Bash => CompletionShell::Bash,
+            ShellOption::Zsh => CompletionShell::Zsh,
+            ShellOption::Fish => CompletionShell::Fish,
+            ShellOption::Nushell => CompletionShell::Nushell,
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum WeztermSubCmd {
+    /// Lua module binding LEADER + register and task keys to their tasks
+    Keys {
+        /// How a key binding starts its task
+        #[arg(short, long, default_value = "spawn")]
+        action: KeyActionOption,
+    },
+
+    /// Command palette entries for an augment-command-palette handler
+    Palette {
+        /// Output format
+        #[arg(short, long, default_value = "json")]
+        format: ExportFormatOption,
+    },
+
+    /// Entries for the launch_menu
+    LaunchMenu {
+        /// Output format
+        #[arg(short, long, default_value = "json")]
+        format: ExportFormatOption,
+    },
+}
+
+#[derive(ValueEnum, Debug, Clone)]
+enum ExportFormatOption {
+    Json,
+    Lua,
+}
+
+impl ExportFormatOption {
+    fn to_export_format(&self) -> ExportFormat {
+        match self {
+            ExportFormatOption::Json => ExportFormat::Json,
+            ExportFormatOption::Lua => ExportFormat::Lua,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone)]
+enum KeyActionOption {
+    Spawn,
+    Event,
+}
+
+impl KeyActionOption {
+    fn to_key_action(&self) -> KeyAction {
+        match self {
+            KeyActionOption::Spawn => KeyAction::Spawn,
+            KeyActionOption::Event => KeyAction::Event,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone)]
+enum BackendOption {
+    Wezterm,
+    Tmux,
+    Local,
+}
+
+impl BackendOption {
+    fn to_backend_kind(&self) -> BackendKind {
+        match self {
+            BackendOption::Wezterm => BackendKind::WezTerm,
+            BackendOption::Tmux => BackendKind::Tmux,
+            BackendOption::Local => BackendKind::Local,
+        }
+    }
 }
 
 #[derive(ValueEnum, Debug, Clone)]
@@ -77,24 +294,126 @@ impl TaskDirectionOption {
 #[derive(Debug, Subcommand)]
 enum ConfigSubCmd {
     Create {},
-    View {},
+    View {
+        /// Output format
+        #[arg(short, long, default_value = "text")]
+        format: ConfigFormatOption,
+    },
+}
+
+#[derive(ValueEnum, Debug, Clone)]
+enum ConfigFormatOption {
+    Text,
+    Table,
+    Json,
+    Toml,
+}
+
+impl ConfigFormatOption {
+    fn to_config_format(&self) -> ConfigFormat {
+        match self {
+            ConfigFormatOption::Text => ConfigFormat::Text,
+            ConfigFormatOption::Table => ConfigFormat::Table,
+            ConfigFormatOption::Json => ConfigFormat::Json,
+            ConfigFormatOption::Toml => ConfigFormat::Toml,
+        }
+    }
+}
+
+fn parse_timeout(value: &str) -> Result<Duration, String> {
+    parse_duration(value).map_err(|err| err.to_string())
+}
+
+/// Split a `--param NAME=VALUE` flag
+fn parse_param(flag: &str) -> Result<(String, String), String> {
+    match flag.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("expected NAME=VALUE, got '{}'", flag)),
+    }
 }
 
-pub struct CliAdapter<C: ConfigPort, P: TerminalPort> {
+/// The `--param` values each selected task takes. A single task takes them all,
+/// several tasks each take those they declare.
+fn given_params(
+    config: &ProjectConfig,
+    selections: &[Selection],
+    params: Vec<(String, String)>,
+) -> Result<Vec<Vec<(String, String)>>, DomainError> {
+    if let [_] = selections {
+        return Ok(vec![params]);
+    }
+    let declares = |selection: &Selection, name: &str| {
+        config
+            .tasks
+            .get(&selection.name)
+            .is_some_and(|command| command.params.iter().any(|param| param.name == name))
+    };
+    if let Some((name, _)) = params
+        .iter()
+        .find(|(name, _)| !selections.iter().any(|selection| declares(selection, name)))
+    {
+        let tasks = selections
+            .iter()
+            .map(|selection| selection.name.as_str())
+            .collect::<Vec<_>>();
+        return Err(DomainError::UnknownParam(tasks.join(", "), name.clone()));
+    }
+    Ok(selections
+        .iter()
+        .map(|selection| {
+            params
+                .iter()
+                .filter(|(name, _)| declares(selection, name))
+                .cloned()
+                .collect()
+        })
+        .collect())
+}
+
+pub struct CliAdapter<
+    C: ConfigPort,
+    P: TerminalPort,
+    S: StatePort,
+    D: DiagnosticsPort,
+    K: PromptPort,
+    E: EnvironmentPort,
+> {
     config_manager: C,
-    task_service: TaskExecutionService<P>,
+    task_service: TaskExecutionService<P, S>,
+    diagnostics_service: DiagnosticsService<D>,
+    variable_service: VariableService<E>,
+    backend: BackendSelection,
+    prompt: K,
 }
 
-impl<C: ConfigPort, P: TerminalPort> CliAdapter<C, P> {
-    pub fn new(config_manager: C, task_service: TaskExecutionService<P>) -> Self {
+impl<
+        C: ConfigPort,
+        P: TerminalPort,
+        S: StatePort,
+        D: DiagnosticsPort,
+        K: PromptPort,
+        E: EnvironmentPort,
+    > CliAdapter<C, P, S, D, K, E>
+{
+    pub fn new(
+        config_manager: C,
+        task_service: TaskExecutionService<P, S>,
+        diagnostics_service: DiagnosticsService<D>,
+        variable_service: VariableService<E>,
+        backend: BackendSelection,
+        prompt: K,
+    ) -> Self {
         Self {
             config_manager,
             task_service,
+            diagnostics_service,
+            variable_service,
+            backend,
+            prompt,
         }
     }
 
-    pub fn run(&self) -> Result<ExitStatus> {
-        let cli = Cli::parse();
+    pub fn run(&self, cli: Cli) -> Result<ExitStatus> {
         self.handle_command(cli)
     }
 
@@ -102,21 +421,82 @@ impl<C: ConfigPort, P: TerminalPort> CliAdapter<C, P> {
         info!("Matching application command");
         match cli.cmd {
             CliSubCmd::TaskRunner {
-                name,
+                names,
                 close,
                 direction,
+                params,
+                keep_going,
+                parallel,
+                timeout,
+                extra_args,
             } => {
                 info!("Command: TaskRunner");
-                info!("Find command ({:?}) in config file", name);
-                let tasks_config = self.config_manager.load_config()?;
-                let task = self.task_service.find_task(
-                    &name,
-                    &tasks_config,
-                    close.to_task_close(),
-                    direction.to_task_direction(),
-                )?;
-                info!("Executing task.");
-                self.task_service.execute_task(task)
+                let config = self.config_manager.load_config()?;
+                if let [namespace] = names.as_slice() {
+                    if namespace.ends_with(NAMESPACE_SEPARATOR) {
+                        info!("List namespace {:?}", namespace);
+                        println!("{}", task_list(&in_namespace(&config, namespace)?, false));
+                        return Ok(ExitStatus::from_raw(0));
+                    }
+                }
+                let (close, direction) = (close.to_task_close(), direction.to_task_direction());
+                let selections = if names.is_empty() {
+                    match self.pick_task(&config, close, direction)? {
+                        Some(selection) => vec![selection],
+                        // Cancelled, exit like an interrupted program
+                        None => return Ok(ExitStatus::from_raw(130 << 8)),
+                    }
+                } else {
+                    names
+                        .iter()
+                        .map(|name| {
+                            Ok(Selection {
+                                name: resolve_task_name(&config, name)?,
+                                close,
+                                direction,
+                            })
+                        })
+                        .collect::<Result<Vec<_>>>()?
+                };
+                let given = given_params(&config, &selections, params)?;
+
+                let mut tasks = vec![];
+                for (selection, params) in selections.into_iter().zip(given) {
+                    match self.prepare_task(&config, selection, &params, &extra_args)? {
+                        Some(mut task) => {
+                            task.settings.timeout = timeout.or(task.settings.timeout);
+                            tasks.push(task);
+                        }
+                        None => return Ok(ExitStatus::from_raw(130 << 8)),
+                    }
+                }
+                if tasks.len() == 1 {
+                    info!("Executing task.");
+                    return self.task_service.execute_task(tasks.remove(0));
+                }
+
+                info!("Executing {} tasks.", tasks.len());
+                let outcomes = self.task_service.execute_tasks(tasks, keep_going, parallel);
+                println!("{}", run_summary(&outcomes));
+                match outcomes
+                    .into_iter()
+                    .find(|outcome| !outcome.succeeded())
+                    .and_then(|outcome| outcome.result)
+                {
+                    Some(result) => result,
+                    None => Ok(ExitStatus::from_raw(0)),
+                }
+            }
+            CliSubCmd::Task { cmd } => {
+                info!("Command: Task");
+                match cmd {
+                    TaskSubCmd::List { all } => {
+                        info!("Sub Command: List");
+                        let config = self.config_manager.load_config()?;
+                        println!("{}", task_list(&config, all));
+                        Ok(ExitStatus::from_raw(0))
+                    }
+                }
             }
             CliSubCmd::Config { cmd } => {
                 info!("Command: Config");
@@ -126,43 +506,281 @@ impl<C: ConfigPort, P: TerminalPort> CliAdapter<C, P> {
                         self.config_manager.create_default_config()?;
                         Ok(ExitStatus::from_raw(0))
                     }
-                    ConfigSubCmd::View {} => {
+                    ConfigSubCmd::View { format } => {
                         info!("Sub Command: View");
-                        let config_str = self.config_manager.view_config()?;
-                        println!("{}", config_str);
+                        let config_str =
+                            self.config_manager.view_config(format.to_config_format())?;
+                        print!("{}", config_str);
                         Ok(ExitStatus::from_raw(0))
                     }
                 }
             }
+            CliSubCmd::Doctor {} => {
+                info!("Command: Doctor");
+                let (report, failure) = self.doctor();
+                println!("{}", report);
+                let code = failure.map_or(0, |kind| i32::from(kind.exit_code()));
+                Ok(ExitStatus::from_raw(code << 8))
+            }
+            CliSubCmd::Wezterm { cmd } => {
+                info!("Command: Wezterm");
+                match cmd {
+                    WeztermSubCmd::Keys { action } => {
+                        info!("Sub Command: Keys");
+                        let config = self.config_manager.load_config()?;
+                        println!("{}", keys_module(&config, action.to_key_action())?);
+                        Ok(ExitStatus::from_raw(0))
+                    }
+                    WeztermSubCmd::Palette { format } => {
+                        info!("Sub Command: Palette");
+                        let config = self.config_manager.load_config()?;
+                        let root = self.config_manager.project_root();
+                        println!("{}", palette(&config, &root, format.to_export_format())?);
+                        Ok(ExitStatus::from_raw(0))
+                    }
+                    WeztermSubCmd::LaunchMenu { format } => {
+                        info!("Sub Command: LaunchMenu");
+                        let config = self.config_manager.load_config()?;
+                        let root = self.config_manager.project_root();
+                        println!(
+                            "{}",
+                            launch_menu(&config, &root, format.to_export_format())?
+                        );
+                        Ok(ExitStatus::from_raw(0))
+                    }
+                }
+            }
+            CliSubCmd::Completions { shell } => {
+                info!("Command: Completions");
+                print!(
+                    "{}",
+                    completion_script(shell.to_completion_shell(), &Cli::command())
+                );
+                Ok(ExitStatus::from_raw(0))
+            }
+            CliSubCmd::Complete { cmd } => match cmd {
+                CompleteSubCmd::Tasks {} => {
+                    // Completing outside a project offers nothing rather than an error
+                    if let Ok(config) = self.config_manager.load_config() {
+                        for name in task_names(&config) {
+                            println!("{}", name);
+                        }
+                    }
+                    Ok(ExitStatus::from_raw(0))
+                }
+            },
+            CliSubCmd::Statusline {} => {
+                info!("Command: Statusline");
+                let runs = self.task_service.task_runs()?;
+                println!("{}", statusline(&runs));
+                Ok(ExitStatus::from_raw(0))
+            }
+            CliSubCmd::History {
+                task,
+                failed,
+                limit,
+                json,
+            } => {
+                info!("Command: History");
+                let mut entries = self
+                    .task_service
+                    .history()?
+                    .into_iter()
+                    .filter(|entry| task.as_ref().is_none_or(|task| &entry.name == task))
+                    .filter(|entry| !failed || !entry.succeeded())
+                    .collect::<Vec<_>>();
+                entries.drain(..entries.len().saturating_sub(limit));
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&entries)?);
+                } else if !entries.is_empty() {
+                    println!("{}", history_list(&entries, unix_now()));
+                }
+                Ok(ExitStatus::from_raw(0))
+            }
+            CliSubCmd::Again {} => {
+                info!("Command: Again");
+                let entry = self
+                    .task_service
+                    .history()?
+                    .pop()
+                    .ok_or(DomainError::NoHistory)?;
+                let config = self.config_manager.load_config()?;
+                let selection = Selection {
+                    name: resolve_task_name(&config, &entry.name)?,
+                    close: entry.close,
+                    direction: entry.direction,
+                };
+                let params = entry.params.into_iter().collect::<Vec<_>>();
+                match self.prepare_task(&config, selection, &params, &entry.extra_args)? {
+                    Some(mut task) => {
+                        let timeout = entry.timeout.map(|timeout| timeout.0);
+                        task.settings.timeout = timeout.or(task.settings.timeout);
+                        self.task_service.execute_task(task)
+                    }
+                    None => Ok(ExitStatus::from_raw(130 << 8)),
+                }
+            }
+        }
+    }
+
+    /// Pick a task with the fuzzy finder, `None` when cancelled
+    fn pick_task(
+        &self,
+        config: &ProjectConfig,
+        close: TaskClose,
+        direction: Direction,
+    ) -> Result<Option<Selection>> {
+        if !self.prompt.is_interactive() {
+            return Err(DomainError::MissingTaskName.into());
+        }
+        // The last run is a nicety, a missing state directory is no reason to fail
+        let runs = self.task_service.task_runs().unwrap_or_default();
+        Picker::new(picker_items(config, &runs), close, direction).pick(&self.prompt)
+    }
+
+    /// Expand, fill in the params of and find the selected task,
+    /// `None` when a param prompt was cancelled
+    fn prepare_task(
+        &self,
+        config: &ProjectConfig,
+        selection: Selection,
+        params: &[(String, String)],
+        extra_args: &[String],
+    ) -> Result<Option<Task>> {
+        info!("Expand variables of {:?}", selection.name);
+        let config = self
+            .variable_service
+            .expand(config.clone(), &selection.name)?;
+        info!("Resolve params of {:?}", selection.name);
+        let params = match self.fill_params(&config, &selection.name, params)? {
+            Some(params) => params,
+            None => return Ok(None),
+        };
+        info!("Find command ({:?}) in config file", selection.name);
+        let task = self.task_service.find_task(
+            &selection.name,
+            &config,
+            selection.close,
+            selection.direction,
+            extra_args,
+            &params,
+        )?;
+        Ok(Some(task))
+    }
+
+    /// Resolve the values of the task's params, `None` when their prompt was cancelled
+    fn fill_params(
+        &self,
+        config: &ProjectConfig,
+        task_name: &str,
+        given: &[(String, String)],
+    ) -> Result<Option<BTreeMap<String, String>>> {
+        match config.tasks.get(task_name) {
+            Some(command) => resolve_params(task_name, &command.params, given, &self.prompt),
+            None => Ok(Some(BTreeMap::new())),
         }
     }
+
+    /// Human readable report and whether every check passed
+    fn doctor(&self) -> (String, Option<FailureKind>) {
+        let checks = self
+            .diagnostics_service
+            .run_checks(self.backend.kind, self.config_manager.load_config());
+        let failure = checks
+            .iter()
+            .find(|check| check.status == CheckStatus::Error)
+            .map(Check::failure_kind);
+
+        let environment = &self.backend.environment;
+        let describe = |value: &Option<String>| match value {
+            Some(value) => value.clone(),
+            None => "not set".to_string(),
+        };
+
+        let report = [
+            format!("Backend: {}", self.backend.kind),
+            format!("  Reason: {}", self.backend.reason),
+            "Environment:".to_string(),
+            format!("  WEZTERM_PANE: {}", describe(&environment.wezterm_pane)),
+            format!("  TMUX: {}", describe(&environment.tmux)),
+            format!("  TTY: {}", if environment.is_tty { "yes" } else { "no" }),
+            format!(
+                "  wezterm binary: {}",
+                environment
+                    .wezterm_binary
+                    .clone()
+                    .unwrap_or_else(|| "not found on PATH".to_string())
+            ),
+        ]
+        .into_iter()
+        .chain(std::iter::once("Checks:".to_string()))
+        .chain(checks.iter().map(|check| format!("  {}", check)))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+        (report, failure)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::domain::models::Command;
-    use crate::ports::mock::{MockConfigPort, MockTerminalPort};
-    use std::collections::HashMap;
+    use crate::adapters::backend_registry::Environment;
+    use crate::domain::models::TaskConfig;
+    use crate::domain::models::{
+        Command, HistoryEntry, Interval, Key, Param, ParamType, ProjectConfig, RunStatus, TaskRun,
+    };
+    use crate::ports::mock::{
+        MockConfigPort, MockDiagnosticsPort, MockEnvironmentPort, MockPromptPort, MockStatePort,
+        MockTerminalPort,
+    };
     use std::os::unix::process::ExitStatusExt;
 
+    // Helper to create a backend selection for the adapter
+    fn local_backend() -> BackendSelection {
+        BackendSelection {
+            kind: BackendKind::Local,
+            reason: "selected with the --backend flag".to_string(),
+            environment: Environment::default(),
+        }
+    }
+
+    // Helper to create a diagnostics service without any wezterm
+    fn setup_diagnostics() -> DiagnosticsService<MockDiagnosticsPort> {
+        let mut mock_diagnostics = MockDiagnosticsPort::new();
+        mock_diagnostics
+            .expect_find_executable()
+            .returning(|_| None);
+        mock_diagnostics.expect_current_pane().returning(|| None);
+        mock_diagnostics.expect_config_path().returning(|| None);
+        mock_diagnostics
+            .expect_check_state_dir()
+            .returning(|| Ok("/tmp/wez-bits".to_string()));
+
+        DiagnosticsService::new(mock_diagnostics)
+    }
+
     // Helper to create a mock config port
     fn setup_mock_config_port() -> MockConfigPort {
         let mut mock_config = MockConfigPort::new();
 
         // Setup default behavior
-        let mut task_config = HashMap::new();
+        let mut task_config = TaskConfig::new();
         task_config.insert(
             "test".to_string(),
             Command {
                 program: "echo".to_string(),
                 args: vec!["test".to_string()],
+                ..Default::default()
             },
         );
 
-        mock_config
-            .expect_load_config()
-            .returning(move || Ok(task_config.clone()));
+        mock_config.expect_load_config().returning(move || {
+            Ok(ProjectConfig {
+                tasks: task_config.clone(),
+                ..Default::default()
+            })
+        });
 
         mock_config
             .expect_create_default_config()
@@ -170,7 +788,11 @@ mod tests {
 
         mock_config
             .expect_view_config()
-            .returning(|| Ok("[test] echo test\n".to_string()));
+            .returning(|_| Ok("[test] echo test\n".to_string()));
+
+        mock_config
+            .expect_project_root()
+            .returning(|| "/work/project".to_string());
 
         mock_config
     }
@@ -185,23 +807,77 @@ mod tests {
 
         mock_terminal.expect_close_pane().returning(|_| Ok(()));
 
+        mock_terminal
+            .expect_set_pane_title()
+            .returning(|_, _| Ok(()));
+
+        mock_terminal
+            .expect_set_tab_title()
+            .returning(|_, _| Ok(()));
+
         mock_terminal
             .expect_pipe_text_to_pane()
             .returning(|_, _| Ok(ExitStatus::from_raw(0)));
 
+        mock_terminal.expect_notify().returning(|_, _, _| Ok(()));
+
+        mock_terminal
+            .expect_set_user_var()
+            .returning(|_, _, _| Ok(()));
+
         mock_terminal
     }
 
+    // Helper to create a prompt for scripts, where stdin is not a terminal
+    fn setup_mock_prompt() -> MockPromptPort {
+        let mut mock_prompt = MockPromptPort::new();
+        mock_prompt.expect_is_interactive().returning(|| false);
+        mock_prompt
+    }
+
+    // Helper to create a mock state port with a running and a failed task
+    fn setup_mock_state() -> MockStatePort {
+        let mut mock_state = MockStatePort::new();
+
+        mock_state.expect_record_run().returning(|_| Ok(()));
+        mock_state.expect_append_history().returning(|_| Ok(()));
+
+        mock_state.expect_load_runs().returning(|| {
+            let run = |name: &str, status: RunStatus| TaskRun {
+                name: name.to_string(),
+                status,
+                pane_id: "1".to_string(),
+                pid: 1,
+                started_at: 0,
+                duration_ms: None,
+                exit_code: None,
+            };
+            Ok(vec![
+                run("build", RunStatus::Running),
+                run("lint", RunStatus::Failed),
+            ])
+        });
+
+        mock_state
+    }
+
     #[test]
     fn test_cli_adapter_initialization() {
         let mock_config = setup_mock_config_port();
         let mock_terminal = setup_mock_terminal();
 
-        let task_service = TaskExecutionService::new(mock_terminal);
+        let task_service = TaskExecutionService::new(mock_terminal, setup_mock_state());
         // Verify adapter can be created
-        CliAdapter::new(mock_config, task_service);
+        let adapter = CliAdapter::new(
+            mock_config,
+            task_service,
+            setup_diagnostics(),
+            VariableService::new(MockEnvironmentPort::new()),
+            local_backend(),
+            setup_mock_prompt(),
+        );
 
-        assert!(true);
+        assert!(adapter.doctor().0.contains("Backend: local"));
     }
 
     #[test]
@@ -214,14 +890,23 @@ mod tests {
 
         let mock_terminal = setup_mock_terminal();
 
-        let task_service = TaskExecutionService::new(mock_terminal);
-        let adapter = CliAdapter::new(mock_config, task_service);
+        let task_service = TaskExecutionService::new(mock_terminal, setup_mock_state());
+        let adapter = CliAdapter::new(
+            mock_config,
+            task_service,
+            setup_diagnostics(),
+            VariableService::new(MockEnvironmentPort::new()),
+            local_backend(),
+            setup_mock_prompt(),
+        );
 
         // Create a Config Create command
         let cli = Cli {
             cmd: CliSubCmd::Config {
                 cmd: ConfigSubCmd::Create {},
             },
+            backend: None,
+            directory: None,
         };
 
         // Handle the command
@@ -237,19 +922,31 @@ mod tests {
         let mut mock_config = MockConfigPort::new();
         mock_config
             .expect_view_config()
+            .with(mockall::predicate::eq(ConfigFormat::Json))
             .times(1)
-            .returning(|| Ok("[test] echo test\n".to_string()));
+            .returning(|_| Ok("{}\n".to_string()));
 
         let mock_terminal = setup_mock_terminal();
 
-        let task_service = TaskExecutionService::new(mock_terminal);
-        let adapter = CliAdapter::new(mock_config, task_service);
+        let task_service = TaskExecutionService::new(mock_terminal, setup_mock_state());
+        let adapter = CliAdapter::new(
+            mock_config,
+            task_service,
+            setup_diagnostics(),
+            VariableService::new(MockEnvironmentPort::new()),
+            local_backend(),
+            setup_mock_prompt(),
+        );
 
         // Create a Config View command
         let cli = Cli {
             cmd: CliSubCmd::Config {
-                cmd: ConfigSubCmd::View {},
+                cmd: ConfigSubCmd::View {
+                    format: ConfigFormatOption::Json,
+                },
             },
+            backend: None,
+            directory: None,
         };
 
         // Handle the command
@@ -264,42 +961,94 @@ mod tests {
     fn test_handle_task_runner_command() {
         // Setup mock config that returns a task config with a "build" task
         let mut mock_config = MockConfigPort::new();
-        let mut task_config = HashMap::new();
+        let mut task_config = TaskConfig::new();
         task_config.insert(
             "build".to_string(),
             Command {
                 program: "npm".to_string(),
                 args: vec!["run".to_string(), "build".to_string()],
+                ..Default::default()
             },
         );
 
         mock_config
             .expect_load_config()
             .times(1)
-            .returning(move || Ok(task_config.clone()));
+            .returning(move || {
+                Ok(ProjectConfig {
+                    tasks: task_config.clone(),
+                    ..Default::default()
+                })
+            });
 
         // Setup mock terminal for task execution
         let mut mock_terminal = MockTerminalPort::new();
+        mock_terminal
+            .expect_set_user_var()
+            .returning(|_, _, _| Ok(()));
         mock_terminal
             .expect_open_pane()
             .times(1)
             .returning(|_, _| Ok("test-pane-id".to_string()));
 
+        mock_terminal
+            .expect_pipe_text_to_pane()
+            .times(1)
+            .returning(|_, _| Ok(ExitStatus::from_raw(0)));
+
         mock_terminal
             .expect_close_pane()
             .times(1)
             .returning(|_| Ok(()));
 
-        let task_service = TaskExecutionService::new(mock_terminal);
-        let adapter = CliAdapter::new(mock_config, task_service);
+        mock_terminal
+            .expect_set_pane_title()
+            .with(
+                mockall::predicate::eq("test-pane-id"),
+                mockall::predicate::function(|title: &str| title.starts_with("▶ build")),
+            )
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        mock_terminal
+            .expect_set_pane_title()
+            .with(
+                mockall::predicate::eq("test-pane-id"),
+                mockall::predicate::function(|title: &str| title.starts_with("✔ build (")),
+            )
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        // Start, finish and the reset before closing the pane
+        mock_terminal
+            .expect_set_tab_title()
+            .times(3)
+            .returning(|_, _| Ok(()));
+
+        let task_service = TaskExecutionService::new(mock_terminal, setup_mock_state());
+        let adapter = CliAdapter::new(
+            mock_config,
+            task_service,
+            setup_diagnostics(),
+            VariableService::new(MockEnvironmentPort::new()),
+            local_backend(),
+            setup_mock_prompt(),
+        );
 
         // Create a TaskRunner command for the "build" task
         let cli = Cli {
             cmd: CliSubCmd::TaskRunner {
-                name: "build".to_string(),
+                names: vec!["build".to_string()],
                 close: TaskCloseOption::OnSuccess,
                 direction: TaskDirectionOption::Right,
+                params: vec![],
+                keep_going: false,
+                parallel: NonZeroUsize::MIN,
+                timeout: None,
+                extra_args: vec![],
             },
+            backend: None,
+            directory: None,
         };
 
         // Handle the command
@@ -310,6 +1059,181 @@ mod tests {
         assert_eq!(result.unwrap().code(), Some(0));
     }
 
+    #[test]
+    fn test_handle_doctor_command() {
+        let adapter = CliAdapter::new(
+            setup_mock_config_port(),
+            TaskExecutionService::new(setup_mock_terminal(), setup_mock_state()),
+            setup_diagnostics(),
+            VariableService::new(MockEnvironmentPort::new()),
+            local_backend(),
+            setup_mock_prompt(),
+        );
+
+        let cli = Cli {
+            cmd: CliSubCmd::Doctor {},
+            backend: None,
+            directory: None,
+        };
+
+        // No config file in the mocked environment, wezterm isn't needed locally
+        let result = adapter.handle_command(cli);
+        assert_eq!(result.unwrap().code(), Some(78));
+        let (report, failure) = adapter.doctor();
+        assert_eq!(failure, Some(FailureKind::Configuration));
+        assert!(report.contains("--backend flag"));
+        assert!(!report.contains("wezterm:"));
+
+        let adapter = CliAdapter::new(
+            setup_mock_config_port(),
+            TaskExecutionService::new(setup_mock_terminal(), setup_mock_state()),
+            setup_diagnostics(),
+            VariableService::new(MockEnvironmentPort::new()),
+            BackendSelection {
+                kind: BackendKind::WezTerm,
+                ..local_backend()
+            },
+            setup_mock_prompt(),
+        );
+        let (report, failure) = adapter.doctor();
+        assert_eq!(failure, Some(FailureKind::Terminal));
+        assert!(report.contains("wezterm was not found on PATH"));
+    }
+
+    #[test]
+    fn test_task_runner_propagates_task_failure() {
+        let mut mock_terminal = MockTerminalPort::new();
+        mock_terminal
+            .expect_set_user_var()
+            .returning(|_, _, _| Ok(()));
+        mock_terminal
+            .expect_open_pane()
+            .times(1)
+            .returning(|_, _| Ok("test-pane-id".to_string()));
+        mock_terminal
+            .expect_pipe_text_to_pane()
+            .times(1)
+            .returning(|_, _| Ok(ExitStatus::from_raw(101 << 8)));
+        // A failed task keeps its pane open with on-success
+        mock_terminal.expect_close_pane().times(0);
+        mock_terminal
+            .expect_set_pane_title()
+            .returning(|_, _| Err(anyhow::anyhow!("titles are optional")));
+        mock_terminal
+            .expect_set_tab_title()
+            .with(
+                mockall::predicate::always(),
+                mockall::predicate::eq("✘ test (exit 101)"),
+            )
+            .times(1)
+            .returning(|_, _| Ok(()));
+        mock_terminal
+            .expect_set_tab_title()
+            .returning(|_, _| Ok(()));
+
+        let task_service = TaskExecutionService::new(mock_terminal, setup_mock_state());
+        let adapter = CliAdapter::new(
+            setup_mock_config_port(),
+            task_service,
+            setup_diagnostics(),
+            VariableService::new(MockEnvironmentPort::new()),
+            local_backend(),
+            setup_mock_prompt(),
+        );
+
+        let cli = Cli {
+            cmd: CliSubCmd::TaskRunner {
+                names: vec!["test".to_string()],
+                close: TaskCloseOption::OnSuccess,
+                direction: TaskDirectionOption::Right,
+                params: vec![],
+                keep_going: false,
+                parallel: NonZeroUsize::MIN,
+                timeout: None,
+                extra_args: vec![],
+            },
+            backend: None,
+            directory: None,
+        };
+
+        let result = adapter.handle_command(cli);
+        assert_eq!(result.unwrap().code(), Some(101));
+    }
+
+    #[test]
+    fn test_handle_statusline_command() {
+        let adapter = CliAdapter::new(
+            setup_mock_config_port(),
+            TaskExecutionService::new(setup_mock_terminal(), setup_mock_state()),
+            setup_diagnostics(),
+            VariableService::new(MockEnvironmentPort::new()),
+            local_backend(),
+            setup_mock_prompt(),
+        );
+
+        let cli = Cli {
+            cmd: CliSubCmd::Statusline {},
+            backend: None,
+            directory: None,
+        };
+
+        let result = adapter.handle_command(cli);
+        assert_eq!(result.unwrap().code(), Some(0));
+    }
+
+    #[test]
+    fn test_handle_wezterm_keys_command() {
+        let adapter = CliAdapter::new(
+            setup_mock_config_port(),
+            TaskExecutionService::new(setup_mock_terminal(), setup_mock_state()),
+            setup_diagnostics(),
+            VariableService::new(MockEnvironmentPort::new()),
+            local_backend(),
+            setup_mock_prompt(),
+        );
+
+        let cli = Cli {
+            cmd: CliSubCmd::Wezterm {
+                cmd: WeztermSubCmd::Keys {
+                    action: KeyActionOption::Event,
+                },
+            },
+            backend: None,
+            directory: None,
+        };
+
+        let result = adapter.handle_command(cli);
+        assert_eq!(result.unwrap().code(), Some(0));
+    }
+
+    #[test]
+    fn test_handle_wezterm_palette_command() {
+        let adapter = CliAdapter::new(
+            setup_mock_config_port(),
+            TaskExecutionService::new(setup_mock_terminal(), setup_mock_state()),
+            setup_diagnostics(),
+            VariableService::new(MockEnvironmentPort::new()),
+            local_backend(),
+            setup_mock_prompt(),
+        );
+
+        for cmd in [
+            WeztermSubCmd::Palette {
+                format: ExportFormatOption::Lua,
+            },
+            WeztermSubCmd::LaunchMenu {
+                format: ExportFormatOption::Json,
+            },
+        ] {
+            let cli = Cli {
+                cmd: CliSubCmd::Wezterm { cmd },
+                backend: None,
+                directory: None,
+            };
+            assert_eq!(adapter.handle_command(cli).unwrap().code(), Some(0));
+        }
+    }
+
     #[test]
     fn test_task_runner_with_nonexistent_task() {
         // Setup mock config that returns an empty task config
@@ -317,20 +1241,34 @@ mod tests {
         mock_config
             .expect_load_config()
             .times(1)
-            .returning(|| Ok(HashMap::new()));
+            .returning(|| Ok(ProjectConfig::default()));
 
         let mock_terminal = setup_mock_terminal();
 
-        let task_service = TaskExecutionService::new(mock_terminal);
-        let adapter = CliAdapter::new(mock_config, task_service);
+        let task_service = TaskExecutionService::new(mock_terminal, setup_mock_state());
+        let adapter = CliAdapter::new(
+            mock_config,
+            task_service,
+            setup_diagnostics(),
+            VariableService::new(MockEnvironmentPort::new()),
+            local_backend(),
+            setup_mock_prompt(),
+        );
 
         // Create a TaskRunner command for a non-existent task
         let cli = Cli {
             cmd: CliSubCmd::TaskRunner {
-                name: "nonexistent".to_string(),
+                names: vec!["nonexistent".to_string()],
                 close: TaskCloseOption::OnSuccess,
                 direction: TaskDirectionOption::Right,
+                params: vec![],
+                keep_going: false,
+                parallel: NonZeroUsize::MIN,
+                timeout: None,
+                extra_args: vec![],
             },
+            backend: None,
+            directory: None,
         };
 
         // Handle the command
@@ -343,5 +1281,407 @@ mod tests {
         let err = result.unwrap_err();
         let err_string = err.to_string();
         assert!(err_string.contains("nonexistent"));
+        assert_eq!(
+            FailureKind::from_error(&err),
+            FailureKind::TaskNotConfigured
+        );
+    }
+
+    #[test]
+    fn test_task_runner_without_name() {
+        let task_runner = || Cli {
+            cmd: CliSubCmd::TaskRunner {
+                names: vec![],
+                close: TaskCloseOption::OnSuccess,
+                direction: TaskDirectionOption::Right,
+                params: vec![],
+                keep_going: false,
+                parallel: NonZeroUsize::MIN,
+                timeout: None,
+                extra_args: vec![],
+            },
+            backend: None,
+            directory: None,
+        };
+
+        // Scripts get a usage error instead of a picker
+        let adapter = CliAdapter::new(
+            setup_mock_config_port(),
+            TaskExecutionService::new(setup_mock_terminal(), setup_mock_state()),
+            setup_diagnostics(),
+            VariableService::new(MockEnvironmentPort::new()),
+            local_backend(),
+            setup_mock_prompt(),
+        );
+        let err = adapter.handle_command(task_runner()).unwrap_err();
+        assert_eq!(FailureKind::from_error(&err), FailureKind::Usage);
+
+        // On a terminal the picked task runs, in the direction picked
+        let mut keys = vec![Key::Enter, Key::Ctrl('d'), Key::Char('t')];
+        let mut mock_prompt = MockPromptPort::new();
+        mock_prompt.expect_is_interactive().returning(|| true);
+        mock_prompt.expect_start().returning(|| Ok(()));
+        mock_prompt.expect_finish().times(1).returning(|| Ok(()));
+        mock_prompt.expect_size().returning(|| (24, 80));
+        mock_prompt.expect_draw().returning(|_| Ok(()));
+        mock_prompt
+            .expect_read_key()
+            .returning(move || Ok(keys.pop().unwrap()));
+
+        let mut mock_terminal = MockTerminalPort::new();
+        mock_terminal
+            .expect_open_pane()
+            .withf(|direction, _| *direction == Direction::Down)
+            .times(1)
+            .returning(|_, _| Ok("test-pane-id".to_string()));
+        mock_terminal.expect_close_pane().returning(|_| Ok(()));
+        mock_terminal
+            .expect_set_pane_title()
+            .returning(|_, _| Ok(()));
+        mock_terminal
+            .expect_set_tab_title()
+            .returning(|_, _| Ok(()));
+        mock_terminal
+            .expect_set_user_var()
+            .returning(|_, _, _| Ok(()));
+        mock_terminal
+            .expect_pipe_text_to_pane()
+            .withf(|invocation, _| invocation.steps[0].command_line() == "echo test")
+            .times(1)
+            .returning(|_, _| Ok(ExitStatus::from_raw(0)));
+
+        let adapter = CliAdapter::new(
+            setup_mock_config_port(),
+            TaskExecutionService::new(mock_terminal, setup_mock_state()),
+            setup_diagnostics(),
+            VariableService::new(MockEnvironmentPort::new()),
+            local_backend(),
+            mock_prompt,
+        );
+        assert_eq!(
+            adapter.handle_command(task_runner()).unwrap().code(),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_handle_listing_commands() {
+        let adapter = CliAdapter::new(
+            setup_mock_config_port(),
+            TaskExecutionService::new(setup_mock_terminal(), setup_mock_state()),
+            setup_diagnostics(),
+            VariableService::new(MockEnvironmentPort::new()),
+            local_backend(),
+            setup_mock_prompt(),
+        );
+
+        for cmd in [
+            CliSubCmd::Task {
+                cmd: TaskSubCmd::List { all: true },
+            },
+            CliSubCmd::Completions {
+                shell: ShellOption::Zsh,
+            },
+            CliSubCmd::Complete {
+                cmd: CompleteSubCmd::Tasks {},
+            },
+        ] {
+            let cli = Cli {
+                cmd,
+                backend: None,
+                directory: None,
+            };
+            assert_eq!(adapter.handle_command(cli).unwrap().code(), Some(0));
+        }
+    }
+
+    #[test]
+    fn test_task_runner_passes_extra_args() {
+        let mut mock_terminal = MockTerminalPort::new();
+        mock_terminal
+            .expect_open_pane()
+            .returning(|_, _| Ok("test-pane-id".to_string()));
+        mock_terminal.expect_close_pane().returning(|_| Ok(()));
+        mock_terminal
+            .expect_set_pane_title()
+            .returning(|_, _| Ok(()));
+        mock_terminal
+            .expect_set_tab_title()
+            .returning(|_, _| Ok(()));
+        mock_terminal
+            .expect_set_user_var()
+            .returning(|_, _, _| Ok(()));
+        mock_terminal
+            .expect_pipe_text_to_pane()
+            .withf(|invocation, _| invocation.steps[0].args == ["test", "--nocapture", "my module"])
+            .times(1)
+            .returning(|_, _| Ok(ExitStatus::from_raw(0)));
+
+        let adapter = CliAdapter::new(
+            setup_mock_config_port(),
+            TaskExecutionService::new(mock_terminal, setup_mock_state()),
+            setup_diagnostics(),
+            VariableService::new(MockEnvironmentPort::new()),
+            local_backend(),
+            setup_mock_prompt(),
+        );
+        let cli = Cli::try_parse_from([
+            "wzb",
+            "task-runner",
+            "test",
+            "--",
+            "--nocapture",
+            "my module",
+        ])
+        .unwrap();
+
+        assert_eq!(adapter.handle_command(cli).unwrap().code(), Some(0));
+    }
+
+    #[test]
+    fn test_task_runner_substitutes_params() {
+        let mut mock_config = MockConfigPort::new();
+        mock_config.expect_load_config().returning(|| {
+            let mut config = ProjectConfig::default();
+            config.tasks.insert(
+                "deploy".to_string(),
+                Command {
+                    program: "./deploy.sh".to_string(),
+                    args: vec![
+                        "--env={env}".to_string(),
+                        "{count}".to_string(),
+                        "--note={note}".to_string(),
+                    ],
+                    params: vec![
+                        Param {
+                            name: "env".to_string(),
+                            choices: vec!["dev".to_string(), "staging".to_string()],
+                            default: Some("dev".to_string()),
+                            ..Default::default()
+                        },
+                        Param {
+                            name: "count".to_string(),
+                            kind: ParamType::Int,
+                            ..Default::default()
+                        },
+                        // Braces in values aren't read as placeholders
+                        Param {
+                            name: "note".to_string(),
+                            default: Some("{1}".to_string()),
+                            ..Default::default()
+                        },
+                    ],
+                    ..Default::default()
+                },
+            );
+            Ok(config)
+        });
+
+        let mut mock_terminal = MockTerminalPort::new();
+        mock_terminal
+            .expect_open_pane()
+            .returning(|_, _| Ok("test-pane-id".to_string()));
+        mock_terminal.expect_close_pane().returning(|_| Ok(()));
+        mock_terminal
+            .expect_set_pane_title()
+            .returning(|_, _| Ok(()));
+        mock_terminal
+            .expect_set_tab_title()
+            .returning(|_, _| Ok(()));
+        mock_terminal
+            .expect_set_user_var()
+            .returning(|_, _, _| Ok(()));
+        mock_terminal
+            .expect_pipe_text_to_pane()
+            .withf(|invocation, _| invocation.steps[0].args == ["--env=dev", "3", "--note={1}"])
+            .times(1)
+            .returning(|_, _| Ok(ExitStatus::from_raw(0)));
+
+        let adapter = CliAdapter::new(
+            mock_config,
+            TaskExecutionService::new(mock_terminal, setup_mock_state()),
+            setup_diagnostics(),
+            VariableService::new(MockEnvironmentPort::new()),
+            local_backend(),
+            setup_mock_prompt(),
+        );
+
+        let cli = Cli::try_parse_from(["wzb", "task-runner", "deploy", "-p", "count=3"]).unwrap();
+        assert_eq!(adapter.handle_command(cli).unwrap().code(), Some(0));
+
+        // Without a terminal to ask on, a param without default is a usage error
+        let cli = Cli::try_parse_from(["wzb", "task-runner", "deploy"]).unwrap();
+        let err = adapter.handle_command(cli).unwrap_err();
+        assert_eq!(FailureKind::from_error(&err), FailureKind::Usage);
+
+        assert!(Cli::try_parse_from(["wzb", "task-runner", "deploy", "-p", "count"]).is_err());
+    }
+
+    #[test]
+    fn test_task_runner_runs_several_tasks() {
+        let mut mock_config = MockConfigPort::new();
+        mock_config.expect_load_config().returning(|| {
+            let mut config = ProjectConfig::default();
+            config.tasks.insert(
+                "deploy".to_string(),
+                Command {
+                    program: "./deploy.sh".to_string(),
+                    args: vec!["{count}".to_string()],
+                    params: vec![Param {
+                        name: "count".to_string(),
+                        kind: ParamType::Int,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            );
+            config.tasks.insert(
+                "build".to_string(),
+                Command {
+                    program: "make".to_string(),
+                    ..Default::default()
+                },
+            );
+            Ok(config)
+        });
+
+        let mut mock_terminal = MockTerminalPort::new();
+        mock_terminal
+            .expect_open_pane()
+            .returning(|_, _| Ok("test-pane-id".to_string()));
+        mock_terminal.expect_close_pane().returning(|_| Ok(()));
+        mock_terminal
+            .expect_set_pane_title()
+            .returning(|_, _| Ok(()));
+        mock_terminal
+            .expect_set_tab_title()
+            .returning(|_, _| Ok(()));
+        mock_terminal
+            .expect_set_user_var()
+            .returning(|_, _, _| Ok(()));
+        mock_terminal
+            .expect_pipe_text_to_pane()
+            .withf(|invocation, _| invocation.steps[0].args == ["3"])
+            .times(1)
+            .returning(|_, _| Ok(ExitStatus::from_raw(4 << 8)));
+        mock_terminal
+            .expect_pipe_text_to_pane()
+            .withf(|invocation, _| invocation.steps[0].program == "make")
+            .times(1)
+            .returning(|_, _| Ok(ExitStatus::from_raw(0)));
+
+        let adapter = CliAdapter::new(
+            mock_config,
+            TaskExecutionService::new(mock_terminal, setup_mock_state()),
+            setup_diagnostics(),
+            VariableService::new(MockEnvironmentPort::new()),
+            local_backend(),
+            setup_mock_prompt(),
+        );
+
+        // Each task takes the params it declares, the first failure is the exit code
+        let cli = Cli::try_parse_from([
+            "wzb",
+            "task-runner",
+            "deploy",
+            "build",
+            "--keep-going",
+            "-p",
+            "count=3",
+        ])
+        .unwrap();
+        assert_eq!(adapter.handle_command(cli).unwrap().code(), Some(4));
+
+        let cli = Cli::try_parse_from(["wzb", "task-runner", "deploy", "build", "-p", "env=dev"])
+            .unwrap();
+        let err = adapter.handle_command(cli).unwrap_err();
+        assert_eq!(FailureKind::from_error(&err), FailureKind::Usage);
+
+        assert!(Cli::try_parse_from(["wzb", "task-runner", "build", "--parallel", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_again_reruns_last_task() {
+        let mut mock_state = MockStatePort::new();
+        mock_state.expect_record_run().returning(|_| Ok(()));
+        mock_state.expect_append_history().returning(|_| Ok(()));
+        mock_state.expect_load_history().returning(|| {
+            let entry = |name: &str, exit_code: Option<i32>| HistoryEntry {
+                name: name.to_string(),
+                command: "echo test --nocapture".to_string(),
+                cwd: None,
+                started_at: 1_700_000_000,
+                duration_ms: 900,
+                exit_code,
+                timed_out: false,
+                attempt: None,
+                timeout: Some(Interval(Duration::from_secs(600))),
+                pane_id: "1".to_string(),
+                close: TaskClose::Never,
+                direction: Direction::Down,
+                extra_args: vec!["--nocapture".to_string()],
+                params: BTreeMap::new(),
+            };
+            Ok(vec![entry("build", Some(0)), entry("test", Some(101))])
+        });
+
+        let mut mock_terminal = MockTerminalPort::new();
+        mock_terminal
+            .expect_open_pane()
+            .withf(|direction, _| *direction == Direction::Down)
+            .times(1)
+            .returning(|_, _| Ok("test-pane-id".to_string()));
+        mock_terminal
+            .expect_set_pane_title()
+            .returning(|_, _| Ok(()));
+        mock_terminal
+            .expect_set_tab_title()
+            .returning(|_, _| Ok(()));
+        mock_terminal
+            .expect_set_user_var()
+            .returning(|_, _, _| Ok(()));
+        mock_terminal
+            .expect_pipe_text_to_pane()
+            .withf(|invocation, _| {
+                invocation.steps[0].args == ["test", "--nocapture"]
+                    && invocation.timeout == Some(Duration::from_secs(600))
+            })
+            .times(1)
+            .returning(|_, _| Ok(ExitStatus::from_raw(0)));
+        // Kept open like the run it repeats, no close_pane expected
+
+        let adapter = CliAdapter::new(
+            setup_mock_config_port(),
+            TaskExecutionService::new(mock_terminal, mock_state),
+            setup_diagnostics(),
+            VariableService::new(MockEnvironmentPort::new()),
+            local_backend(),
+            setup_mock_prompt(),
+        );
+
+        let cli = Cli::try_parse_from(["wzb", "again"]).unwrap();
+        assert_eq!(adapter.handle_command(cli).unwrap().code(), Some(0));
+
+        let cli = Cli::try_parse_from(["wzb", "history", "test", "--failed", "--json"]).unwrap();
+        assert_eq!(adapter.handle_command(cli).unwrap().code(), Some(0));
+    }
+
+    #[test]
+    fn test_again_without_history() {
+        let mut mock_state = MockStatePort::new();
+        mock_state.expect_load_history().returning(|| Ok(vec![]));
+
+        let adapter = CliAdapter::new(
+            setup_mock_config_port(),
+            TaskExecutionService::new(setup_mock_terminal(), mock_state),
+            setup_diagnostics(),
+            VariableService::new(MockEnvironmentPort::new()),
+            local_backend(),
+            setup_mock_prompt(),
+        );
+
+        let cli = Cli::try_parse_from(["wzb", "again"]).unwrap();
+        let err = adapter.handle_command(cli).unwrap_err();
+        assert_eq!(FailureKind::from_error(&err), FailureKind::Usage);
     }
 }
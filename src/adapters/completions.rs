@@ -0,0 +1,212 @@
+//! Shell completion scripts, completing task names through `wzb __complete tasks`
+
+use clap::Command;
+use clap_complete::{generate, Shell};
+use clap_complete_nushell::Nushell;
+
+use crate::constants::{BIN_NAME, VERSION};
+use crate::domain::models::ProjectConfig;
+
+/// Shells with a completion script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+    Nushell,
+}
+
+/// Completes task names after `task-runner`, other words go to the generated `_wzb`
+const BASH_TASKS: &str = r#"
+_wzb_tasks() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+    if [[ " ${COMP_WORDS[*]:1:COMP_CWORD-1} " == *" task-runner "* && "$cur" != -* ]]; then
+        # Namespaced tasks contain `:`, which bash splits words on
+        local line="${COMP_LINE:0:COMP_POINT}"
+        local word="${line##*[[:space:]]}"
+        case "$prev" in
+            -b|--backend|-C|--directory|-c|--close|-d|--direction|-p|--param|-j|--parallel|--timeout) ;;
+            *)
+                COMPREPLY=($(compgen -W "$(wzb __complete tasks 2>/dev/null)" -- "$word"))
+                if [[ "$word" == *:* ]]; then
+                    local namespace="${word%"${word##*:}"}"
+                    COMPREPLY=("${COMPREPLY[@]#"$namespace"}")
+                fi
+                return 0
+                ;;
+        esac
+    fi
+    _wzb "$@"
+}
+
+complete -F _wzb_tasks -o bashdefault -o default wzb
+"#;
+
+const ZSH_TASKS: &str = r#"
+_wzb_tasks() {
+    local index=${words[(I)task-runner]}
+    if (( index > 1 && index < CURRENT )) && [[ $PREFIX != -* ]] &&
+        [[ ${words[CURRENT-1]} != (-b|--backend|-C|--directory|-c|--close|-d|--direction|-p|--param|-j|--parallel|--timeout) ]]; then
+        local -a tasks
+        tasks=(${(f)"$(wzb __complete tasks 2>/dev/null)"})
+        compadd -a tasks
+        return
+    fi
+    _wzb "$@"
+}
+
+compdef _wzb_tasks wzb
+"#;
+
+const FISH_TASKS: &str = r#"
+complete -c wzb -n "__fish_seen_subcommand_from task-runner" -f -a "(wzb __complete tasks 2>/dev/null)"
+"#;
+
+const NUSHELL_TASKS: &str = r#"
+  def "nu-complete wzb tasks" [] {
+    ^wzb __complete tasks | lines
+  }
+"#;
+
+/// Completion script for `command`, hidden subcommands left out
+pub fn completion_script(shell: CompletionShell, command: &Command) -> String {
+    let mut command = visible_command(command);
+    let mut script = Vec::new();
+    match shell {
+        CompletionShell::Bash => generate(Shell::Bash, &mut command, BIN_NAME, &mut script),
+        CompletionShell::Zsh => generate(Shell::Zsh, &mut command, BIN_NAME, &mut script),
+        CompletionShell::Fish => generate(Shell::Fish, &mut command, BIN_NAME, &mut script),
+        CompletionShell::Nushell => generate(Nushell, &mut command, BIN_NAME, &mut script),
+    }
+    let script = String::from_utf8_lossy(&script);
+
+    match shell {
+        CompletionShell::Bash => format!("{}{}", script, BASH_TASKS),
+        CompletionShell::Zsh => format!("{}{}", script, ZSH_TASKS),
+        CompletionShell::Fish => format!("{}{}", script, FISH_TASKS),
+        CompletionShell::Nushell => nushell_tasks(&script),
+    }
+}
+
+/// Task and register names worth completing, every visible task with something
+/// to run and the aliases it is run by too
+pub fn task_names(config: &ProjectConfig) -> Vec<String> {
+    let mut names = config
+        .tasks
+        .iter()
+        .filter(|(name, command)| command.is_runnable() && !command.is_hidden(name))
+        .flat_map(|(name, command)| [name].into_iter().chain(&command.aliases))
+        .cloned()
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// clap completes hidden subcommands too, rebuild the root without them
+fn visible_command(command: &Command) -> Command {
+    let subcommands = command
+        .get_subcommands()
+        .filter(|subcommand| !subcommand.is_hide_set())
+        .cloned()
+        .collect::<Vec<_>>();
+
+    Command::new(BIN_NAME)
+        .version(VERSION)
+        .args(command.get_arguments().cloned())
+        .subcommands(subcommands)
+}
+
+/// Nushell completes through custom completers named on each parameter
+fn nushell_tasks(script: &str) -> String {
+    let mut in_task_runner = false;
+    let mut lines = vec![];
+    for line in script.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("export extern") {
+            in_task_runner = trimmed.contains("\"wzb task-runner\"");
+        }
+
+        // Positional parameters look like `name?: string    # help`
+        let positional = !trimmed.starts_with('-') && trimmed.contains(": string");
+        if in_task_runner && positional {
+            lines.push(line.replacen(": string", ": string@\"nu-complete wzb tasks\"", 1));
+        } else {
+            lines.push(line.to_string());
+        }
+
+        if trimmed.starts_with("module completions {") {
+            lines.push(NUSHELL_TASKS.trim_end().to_string());
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::Command as TaskCommand;
+    use clap::{Arg, ArgAction};
+
+    fn command() -> Command {
+        Command::new("Wez Bits")
+            .arg(
+                Arg::new("backend")
+                    .short('b')
+                    .long("backend")
+                    .global(true)
+                    .action(ArgAction::Set),
+            )
+            .subcommand(Command::new("task-runner").arg(Arg::new("name").help("Task name")))
+            .subcommand(Command::new("doctor"))
+            .subcommand(Command::new("__complete").hide(true))
+    }
+
+    #[test]
+    fn test_scripts_complete_tasks_without_hidden_subcommands() {
+        for shell in [
+            CompletionShell::Bash,
+            CompletionShell::Zsh,
+            CompletionShell::Fish,
+            CompletionShell::Nushell,
+        ] {
+            let script = completion_script(shell, &command());
+            assert!(script.contains("task-runner"), "{:?}", shell);
+            assert!(script.contains("wzb __complete tasks"), "{:?}", shell);
+            // Only the helper call, the hidden subcommand itself is left out
+            assert_eq!(script.matches("__complete").count(), 1, "{:?}", shell);
+        }
+    }
+
+    #[test]
+    fn test_nushell_names_task_completer() {
+        let script = completion_script(CompletionShell::Nushell, &command());
+        assert!(script.contains("name?: string@\"nu-complete wzb tasks\" "));
+        assert!(script.contains("def \"nu-complete wzb tasks\" []"));
+    }
+
+    #[test]
+    fn test_task_names() {
+        let mut config = ProjectConfig::default();
+        for (name, program) in [
+            ("test", "cargo"),
+            ("q", "htop"),
+            ("w", ""),
+            ("build", "make"),
+            ("_setup", "make"),
+        ] {
+            config.tasks.insert(
+                name.to_string(),
+                TaskCommand {
+                    program: program.to_string(),
+                    ..Default::default()
+                },
+            );
+        }
+
+        config.tasks.get_mut("test").unwrap().aliases = vec!["t".to_string(), "q".to_string()];
+        config.tasks.get_mut("_setup").unwrap().aliases = vec!["setup".to_string()];
+
+        assert_eq!(task_names(&config), ["build", "q", "t", "test"]);
+    }
+}
@@ -1,65 +1,122 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
 use anyhow::{Context, Result};
 use log::info;
+use serde::Serialize;
 
-use crate::domain::models::{ConfigError, TaskConfig};
+use crate::domain::extends::resolve_extends;
+use crate::domain::models::{Command, ConfigError, ConfigFormat, ProjectConfig, Settings};
 use crate::ports::{ConfigPort, FileSystemPort};
 
 const DEFAULT_CONFIG: &str = r#"# WezBits Configuration
 
+# Global settings
+# [settings]
+# backend = "wezterm" # wezterm | tmux | local, detected when omitted
+# notify = "30s"      # always | never | notify when a task runs at least this long
+# shell = "bash"      # interpreter of script tasks
+# on_failure = "..."  # hook of every task, like before, after and on_success below
+
+# Tasks take optional metadata used by `wzb task list`, the picker and exports:
+# description = "..." one line summary
+# group = "..."       heading the task is listed under
+# hidden = true       only run by name, like tasks whose names start with `_`
+# aliases = ["t"]     other names to run it by, besides any unique prefix of its name
+# timeout = "10m"     interrupt, then terminate and kill the task when it runs longer
+# retries = 3         run a failing task again in its pane, after retry_delay = "2s",
+#                     only for the exit codes in retry_on = [98] when given
+# before = "..."      command run in the pane first, the task is skipped when it fails
+# after = "..."       command run once the task finished, after on_success or on_failure,
+#                     seeing WZB_TASK, WZB_STATUS, WZB_EXIT_CODE and WZB_DURATION_MS
+
+# Nested tables such as [rust.build] are namespaced tasks, run as `rust:build`.
+#
+# A task with extends = "other-task" inherits its program, args, env, cwd, timeout,
+# retries, hooks and params, overriding them or adding append_args = [...] after the
+# inherited args.
+#
+# Instead of program and args, a task can run steps in order, stopping at the first
+# failure: steps = [{ program = "cargo", args = ["fmt"], continue_on_error = true }, ...]
+# or an inline script = """...""" run by shell = "bash -euo pipefail" (default sh, or
+# shell in [settings]).
+#
+# Tasks can set environment variables with env = { KEY = "..." }, and ask for
+# inputs used as {name} in args and env, given with --param name=value or prompted:
+# params = [{ name = "env", choices = ["dev", "staging"], default = "dev" }, { name = "count", type = "int" }]
+#
+# program, args, steps, cwd and env expand ${env:NAME}, ${project_root}, ${config_dir},
+# ${git_branch}, ${date} and the variables of a [vars] table, $${ is a literal ${
+# [vars]
+# image = "app:${git_branch}"
+
 # Common tasks
 [build]
 program = "npm"
 args = ["run", "build"]
+group = "common"
 
 [format]
 program = ""
 args = []
+group = "common"
 
 [run]
 program = ""
 args = []
+group = "common"
 
 [test]
 program = ""
 args = []
+group = "common"
 
 [check]
 program = ""
 args = []
+group = "common"
 
 # Interactive registers
 [q]
 program = ""
 args = []
+group = "interactive registers"
 
 [w]
 program = ""
 args = []
+group = "interactive registers"
 
 [e]
 program = ""
 args = []
+group = "interactive registers"
 
 [y]
 program = ""
 args = []
+group = "interactive registers"
 
 # Non-interactive registers
 [Q]
 program = ""
 args = []
+group = "non-interactive registers"
 
 [W]
 program = ""
 args = []
+group = "non-interactive registers"
 
 [E]
 program = ""
 args = []
+group = "non-interactive registers"
 
 [Y]
 program = ""
 args = []
+group = "non-interactive registers"
 "#;
 
 pub struct ConfigAdapter<F: FileSystemPort> {
@@ -79,15 +136,23 @@ impl<F: FileSystemPort> ConfigAdapter<F> {
 }
 
 impl<F: FileSystemPort> ConfigPort for ConfigAdapter<F> {
-    fn load_config(&self) -> Result<TaskConfig> {
-        let path = format!("{}/{}", self.dot_dir, self.config_file);
+    fn load_config(&self) -> Result<ProjectConfig> {
+        let path = self.config_path();
         info!("Load and parse configuration: {}", path);
 
-        let content = self.file_system.read_from_file(&path)?;
+        let content = self.file_system.read_from_file(&path).with_context(|| {
+            ConfigError::Load(format!(
+                "No readable config at {}, run `wzb config create`",
+                path
+            ))
+        })?;
 
-        toml::from_str::<TaskConfig>(&content).with_context(|| {
+        let config = toml::from_str::<ProjectConfig>(&content).with_context(|| {
             ConfigError::Parse(format!("Failed to parse TOML config file: {}", path))
-        })
+        })?;
+        let mut config = resolve_extends(config)?;
+        config.root = self.project_root();
+        Ok(config)
     }
 
     fn create_default_config(&self) -> Result<()> {
@@ -98,7 +163,7 @@ impl<F: FileSystemPort> ConfigPort for ConfigAdapter<F> {
                 ConfigError::Create(format!("Failed to create directory: {}", self.dot_dir))
             })?;
 
-        let path = format!("{}/{}", self.dot_dir, self.config_file);
+        let path = self.config_path();
         info!("Creating {}", path);
 
         info!("Writing default configuration");
@@ -115,24 +180,126 @@ impl<F: FileSystemPort> ConfigPort for ConfigAdapter<F> {
         Ok(())
     }
 
-    fn view_config(&self) -> Result<String> {
-        info!("Viewing config");
+    fn view_config(&self, format: ConfigFormat) -> Result<String> {
+        info!("Viewing config as {:?}", format);
         let config = self.load_config().with_context(|| {
             ConfigError::Load("Failed to load configuration for viewing".to_string())
         })?;
 
-        let output = config
-            .iter()
-            .map(|(key, value)| format!("[{}] {} {}\n", key, value.program, value.args.join(" ")))
-            .collect::<String>();
+        let output = match format {
+            ConfigFormat::Text => config
+                .tasks
+                .iter()
+                .map(|(key, value)| {
+                    if value.steps.is_empty() && value.script.is_none() {
+                        format!("[{}] {} {}\n", key, value.program, value.args.join(" "))
+                    } else {
+                        format!("[{}] {}\n", key, value.command_line())
+                    }
+                })
+                .collect::<String>(),
+            ConfigFormat::Table => table(
+                ["TASK", "PROGRAM", "ARGS", "DESCRIPTION"],
+                config.tasks.iter().map(|(name, command)| {
+                    let (program, args) = if !command.steps.is_empty() {
+                        (
+                            format!("({} steps)", command.steps.len()),
+                            command.command_line(),
+                        )
+                    } else if command.script.is_some() {
+                        ("(script)".to_string(), command.command_line())
+                    } else {
+                        (command.program.clone(), command.args.join(" "))
+                    };
+                    [
+                        name.clone(),
+                        program,
+                        args,
+                        command.description.clone().unwrap_or_default(),
+                    ]
+                }),
+            ),
+            ConfigFormat::Json => {
+                let view = ConfigView {
+                    source: self.config_path(),
+                    root: &config.root,
+                    settings: &config.settings,
+                    vars: &config.vars,
+                    tasks: config
+                        .tasks
+                        .iter()
+                        .map(|(name, command)| TaskView { name, command })
+                        .collect(),
+                };
+                serde_json::to_string_pretty(&view)? + "\n"
+            }
+            ConfigFormat::Toml => toml::to_string_pretty(&config)?,
+        };
 
         Ok(output)
     }
+
+    fn config_path(&self) -> String {
+        format!("{}/{}", self.dot_dir, self.config_file)
+    }
+
+    fn project_root(&self) -> String {
+        Path::new(&self.dot_dir)
+            .parent()
+            .map(|root| root.to_string_lossy().to_string())
+            .filter(|root| !root.is_empty())
+            .unwrap_or_else(|| ".".to_string())
+    }
+}
+
+/// JSON shape of `wzb config view --format json`
+#[derive(Serialize)]
+struct ConfigView<'a> {
+    source: String,
+    root: &'a str,
+    settings: &'a Settings,
+    vars: &'a BTreeMap<String, String>,
+    tasks: Vec<TaskView<'a>>,
+}
+
+#[derive(Serialize)]
+struct TaskView<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    command: &'a Command,
+}
+
+/// Left aligned columns separated by two spaces
+fn table<const N: usize>(header: [&str; N], rows: impl Iterator<Item = [String; N]>) -> String {
+    let rows = std::iter::once(header.map(str::to_string))
+        .chain(rows)
+        .collect::<Vec<_>>();
+    let widths = (0..N)
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+
+    rows.iter()
+        .map(|row| {
+            let line = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ");
+            format!("{}\n", line.trim_end())
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::models::BackendKind;
     use crate::ports::mock::MockFileSystemPort;
 
     fn create_valid_toml_config() -> String {
@@ -167,17 +334,61 @@ args = ["run", "test"]
         assert!(result.is_ok());
 
         let config = result.unwrap();
-        assert_eq!(config.len(), 2);
+        assert_eq!(config.tasks.len(), 2);
+        assert!(config.settings.backend.is_none());
+        assert_eq!(config.root, ".");
 
-        let build_cmd = config.get("build").unwrap();
+        let build_cmd = config.tasks.get("build").unwrap();
         assert_eq!(build_cmd.program, "npm");
         assert_eq!(build_cmd.args, vec!["run", "build"]);
 
-        let test_cmd = config.get("test").unwrap();
+        let test_cmd = config.tasks.get("test").unwrap();
         assert_eq!(test_cmd.program, "npm");
         assert_eq!(test_cmd.args, vec!["run", "test"]);
     }
 
+    #[test]
+    fn test_load_config_with_settings() {
+        let mut mock_fs = MockFileSystemPort::new();
+
+        mock_fs.expect_read_from_file().times(1).returning(|_| {
+            Ok(format!(
+                "[settings]\nbackend = \"tmux\"\n{}",
+                create_valid_toml_config()
+            ))
+        });
+
+        let adapter = ConfigAdapter::new(mock_fs, ".wez".to_string(), "config.toml".to_string());
+
+        let config = adapter.load_config().unwrap();
+        assert_eq!(config.settings.backend, Some(BackendKind::Tmux));
+        assert_eq!(config.tasks.len(), 2);
+        assert!(!config.tasks.contains_key("settings"));
+    }
+
+    #[test]
+    fn test_load_config_resolves_extends() {
+        let mut mock_fs = MockFileSystemPort::new();
+
+        mock_fs.expect_read_from_file().times(2).returning(|_| {
+            Ok(format!(
+                "{}\n[release]\nextends = \"build\"\nappend_args = [\"--prod\"]\n",
+                create_valid_toml_config()
+            ))
+        });
+
+        let adapter = ConfigAdapter::new(mock_fs, ".wez".to_string(), "config.toml".to_string());
+
+        let config = adapter.load_config().unwrap();
+        let release = &config.tasks["release"];
+        assert_eq!(release.program, "npm");
+        assert_eq!(release.args, vec!["run", "build", "--prod"]);
+
+        // Views show the resolved tasks
+        let view = adapter.view_config(ConfigFormat::Text).unwrap();
+        assert!(view.contains("[release] npm run build --prod"));
+    }
+
     #[test]
     fn test_load_config_file_error() {
         let mut mock_fs = MockFileSystemPort::new();
@@ -195,6 +406,7 @@ args = ["run", "test"]
         // Load config
         let result = adapter.load_config();
         assert!(result.is_err());
+        assert!(result.unwrap_err().downcast_ref::<ConfigError>().is_some());
     }
 
     #[test]
@@ -264,6 +476,24 @@ args = ["run", "test"]
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_config_paths() {
+        let adapter = ConfigAdapter::new(
+            MockFileSystemPort::new(),
+            "/work/project/.wez".to_string(),
+            "config.toml".to_string(),
+        );
+        assert_eq!(adapter.config_path(), "/work/project/.wez/config.toml");
+        assert_eq!(adapter.project_root(), "/work/project");
+
+        let adapter = ConfigAdapter::new(
+            MockFileSystemPort::new(),
+            ".wez".to_string(),
+            "config.toml".to_string(),
+        );
+        assert_eq!(adapter.project_root(), ".");
+    }
+
     #[test]
     fn test_view_config() {
         let mut mock_fs = MockFileSystemPort::new();
@@ -279,11 +509,69 @@ args = ["run", "test"]
         let adapter = ConfigAdapter::new(mock_fs, ".wez".to_string(), "config.toml".to_string());
 
         // View config
-        let result = adapter.view_config();
+        let result = adapter.view_config(ConfigFormat::Text);
         assert!(result.is_ok());
 
         let output = result.unwrap();
-        assert!(output.contains("[build] npm run build"));
-        assert!(output.contains("[test] npm run test"));
+        assert_eq!(output, "[build] npm run build\n[test] npm run test\n");
+    }
+
+    #[test]
+    fn test_view_config_formats() {
+        let mut mock_fs = MockFileSystemPort::new();
+        mock_fs.expect_read_from_file().returning(|_| {
+            Ok(format!(
+                "[settings]\nnotify = \"30s\"\n{}\n[lint]\nprogram = \"cargo\"\nargs = [\"clippy\"]\ndescription = \"Run clippy\"\n",
+                create_valid_toml_config()
+            ))
+        });
+        let adapter = ConfigAdapter::new(mock_fs, ".wez".to_string(), "config.toml".to_string());
+
+        assert_eq!(
+            adapter.view_config(ConfigFormat::Table).unwrap(),
+            "TASK   PROGRAM  ARGS       DESCRIPTION\n\
+             build  npm      run build\n\
+             lint   cargo    clippy     Run clippy\n\
+             test   npm      run test\n"
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&adapter.view_config(ConfigFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["source"], ".wez/config.toml");
+        assert_eq!(json["settings"]["notify"], "30s");
+        assert_eq!(json["tasks"][1]["name"], "lint");
+        assert_eq!(json["tasks"][1]["description"], "Run clippy");
+        assert_eq!(json["tasks"][1]["args"][0], "clippy");
+
+        // The TOML form parses back to the same tasks
+        let toml = adapter.view_config(ConfigFormat::Toml).unwrap();
+        let reparsed = toml::from_str::<ProjectConfig>(&toml).unwrap();
+        assert_eq!(
+            reparsed.tasks.keys().collect::<Vec<_>>(),
+            ["build", "lint", "test"]
+        );
+        assert!(toml.starts_with("[settings]"));
+    }
+
+    #[test]
+    fn test_view_config_shows_scripts() {
+        let mut mock_fs = MockFileSystemPort::new();
+        mock_fs.expect_read_from_file().returning(|_| {
+            Ok("[seed]\nscript = \"echo seeded\"\nargs = [\"users\"]\n\n\
+                [report]\nscript = \"print(1)\"\nshell = \"python3\"\n"
+                .to_string())
+        });
+        let adapter = ConfigAdapter::new(mock_fs, ".wez".to_string(), "config.toml".to_string());
+
+        assert_eq!(
+            adapter.view_config(ConfigFormat::Text).unwrap(),
+            "[report] python3 <script>\n[seed] sh <script> users\n"
+        );
+        assert_eq!(
+            adapter.view_config(ConfigFormat::Table).unwrap(),
+            "TASK    PROGRAM   ARGS               DESCRIPTION\n\
+             report  (script)  python3 <script>\n\
+             seed    (script)  sh <script> users\n"
+        );
     }
 }
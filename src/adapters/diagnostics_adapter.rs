@@ -0,0 +1,90 @@
+use std::env;
+use std::fs::{create_dir_all, remove_file, write};
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::{anyhow, Context, Result};
+use log::info;
+
+use crate::adapters::paths::state_dir;
+use crate::constants::{CONFIG_FILE, DOTDIR};
+use crate::domain::models::TerminalError;
+use crate::ports::{DiagnosticsPort, FileSystemPort};
+
+pub struct DiagnosticsAdapter<F: FileSystemPort> {
+    file_system: F,
+    project_root: PathBuf,
+}
+
+impl<F: FileSystemPort> DiagnosticsAdapter<F> {
+    pub fn new(file_system: F, project_root: PathBuf) -> Self {
+        Self {
+            file_system,
+            project_root,
+        }
+    }
+}
+
+impl<F: FileSystemPort> DiagnosticsPort for DiagnosticsAdapter<F> {
+    fn wezterm_version(&self) -> Result<String> {
+        info!("Checking wezterm version");
+        let output = Command::new("wezterm")
+            .arg("--version")
+            .output()
+            .context("Failed to run wezterm --version")?;
+
+        if !output.status.success() {
+            return Err(anyhow!(String::from_utf8_lossy(&output.stderr)
+                .trim()
+                .to_string()));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn list_panes(&self) -> Result<usize> {
+        info!("Listing wezterm panes");
+        let output = Command::new("wezterm")
+            .args(["cli", "list", "--format", "json"])
+            .output()
+            .with_context(|| TerminalError::OpenPane("Failed to run wezterm cli".to_string()))?;
+
+        if !output.status.success() {
+            return Err(anyhow!(String::from_utf8_lossy(&output.stderr)
+                .trim()
+                .to_string()));
+        }
+
+        let panes: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout)
+            .context("Failed to parse wezterm cli list output")?;
+        Ok(panes.len())
+    }
+
+    fn current_pane(&self) -> Option<String> {
+        env::var("WEZTERM_PANE")
+            .ok()
+            .filter(|pane| !pane.is_empty())
+    }
+
+    fn config_path(&self) -> Option<String> {
+        let path = self.project_root.join(DOTDIR).join(CONFIG_FILE);
+        path.is_file().then(|| path.to_string_lossy().to_string())
+    }
+
+    fn find_executable(&self, program: &str) -> Option<String> {
+        self.file_system.find_executable(program)
+    }
+
+    fn check_state_dir(&self) -> Result<String> {
+        let dir = state_dir(&self.project_root)
+            .ok_or_else(|| anyhow!("Neither XDG_STATE_HOME nor HOME is set"))?;
+        let display = dir.to_string_lossy().to_string();
+
+        create_dir_all(&dir).with_context(|| format!("Failed to create {}", display))?;
+        let probe = dir.join(".doctor");
+        write(&probe, "ok").with_context(|| format!("Failed to write to {}", display))?;
+        remove_file(&probe).with_context(|| format!("Failed to clean up {}", display))?;
+
+        Ok(display)
+    }
+}
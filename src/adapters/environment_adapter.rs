@@ -0,0 +1,49 @@
+use std::env;
+use std::process::Command;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::ports::EnvironmentPort;
+
+/// Reads built-in variable values from the process environment, git and `date`
+pub struct EnvironmentAdapter {}
+
+impl EnvironmentAdapter {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl EnvironmentPort for EnvironmentAdapter {
+    fn var(&self, name: &str) -> Option<String> {
+        env::var(name).ok()
+    }
+
+    fn git_branch(&self, dir: &str) -> Result<String> {
+        // A detached HEAD has no branch, name its commit instead
+        stdout(Command::new("git").args(["-C", dir, "symbolic-ref", "--short", "HEAD"]))
+            .or_else(|_| {
+                stdout(Command::new("git").args(["-C", dir, "rev-parse", "--short", "HEAD"]))
+            })
+            .with_context(|| format!("No git branch checked out in {}", dir))
+    }
+
+    fn date(&self) -> Result<String> {
+        stdout(Command::new("date").arg("+%F")).context("Failed to read the date")
+    }
+}
+
+/// Trimmed stdout of a command, failing on a non-zero exit
+fn stdout(command: &mut Command) -> Result<String> {
+    let output = command.output()?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(anyhow!(stderr
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_string()))
+    }
+}
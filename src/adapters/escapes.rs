@@ -0,0 +1,106 @@
+//! Operating System Command escape sequences understood by WezTerm
+
+/// OSC 2, sets the window/pane title
+pub fn osc_title(title: &str) -> String {
+    format!("\x1b]2;{}\x1b\\", sanitize(title))
+}
+
+/// OSC 777, a desktop notification with a title and body
+pub fn osc_notify(title: &str, body: &str) -> String {
+    // `;` separates the fields of the sequence
+    format!(
+        "\x1b]777;notify;{};{}\x1b\\",
+        sanitize(title).replace(';', ","),
+        sanitize(body).replace(';', ",")
+    )
+}
+
+/// OSC 9, a body only notification understood by more terminals
+pub fn osc9_notify(body: &str) -> String {
+    format!("\x1b]9;{}\x1b\\", sanitize(body))
+}
+
+/// OSC 1337 `SetUserVar`, the value is base64 encoded as WezTerm expects
+pub fn osc_user_var(name: &str, value: &str) -> String {
+    format!(
+        "\x1b]1337;SetUserVar={}={}\x07",
+        sanitize(name).replace('=', "_"),
+        base64(value.as_bytes())
+    )
+}
+
+/// Wrap a sequence so tmux forwards it to the outer terminal
+pub fn tmux_passthrough(sequence: &str) -> String {
+    format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    bytes
+        .chunks(3)
+        .flat_map(|chunk| {
+            let group = chunk
+                .iter()
+                .enumerate()
+                .fold(0u32, |acc, (i, byte)| acc | (*byte as u32) << (16 - 8 * i));
+            (0..4).map(move |i| {
+                if i <= chunk.len() {
+                    ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char
+                } else {
+                    '='
+                }
+            })
+        })
+        .collect()
+}
+
+/// Control characters would terminate the sequence early
+fn sanitize(text: &str) -> String {
+    text.chars().filter(|c| !c.is_control()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osc_title() {
+        assert_eq!(osc_title("▶ test"), "\x1b]2;▶ test\x1b\\");
+        assert_eq!(osc_title("bad\x07title\n"), "\x1b]2;badtitle\x1b\\");
+    }
+
+    #[test]
+    fn test_osc_notify() {
+        assert_eq!(
+            osc_notify("wzb: test", "failed; exit 1"),
+            "\x1b]777;notify;wzb: test;failed, exit 1\x1b\\"
+        );
+        assert_eq!(osc9_notify("done"), "\x1b]9;done\x1b\\");
+    }
+
+    #[test]
+    fn test_osc_user_var() {
+        assert_eq!(
+            osc_user_var("wzb_status", "running"),
+            "\x1b]1337;SetUserVar=wzb_status=cnVubmluZw==\x07"
+        );
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64("▶ test".as_bytes()), "4pa2IHRlc3Q=");
+    }
+
+    #[test]
+    fn test_tmux_passthrough() {
+        assert_eq!(
+            tmux_passthrough("\x1b]9;done\x1b\\"),
+            "\x1bPtmux;\x1b\x1b]9;done\x1b\x1b\\\x1b\\"
+        );
+    }
+}
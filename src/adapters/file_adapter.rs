@@ -1,5 +1,8 @@
+use std::env;
 use std::fs::{create_dir, File};
 use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
 
 use anyhow::{Context, Result};
 use log::info;
@@ -38,6 +41,24 @@ impl FileSystemPort for FileAdapter {
         info!("Reading from file: {}", path);
         std::fs::read_to_string(path).with_context(|| FileSystemError::Read(path.to_string()))
     }
+
+    fn find_executable(&self, program: &str) -> Option<String> {
+        if program.contains('/') {
+            return is_executable(Path::new(program)).then(|| program.to_string());
+        }
+
+        let paths = env::var_os("PATH")?;
+        env::split_paths(&paths)
+            .map(|dir| dir.join(program))
+            .find(|candidate| is_executable(candidate))
+            .map(|found| found.to_string_lossy().to_string())
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
 }
 
 #[cfg(test)]
@@ -74,6 +95,15 @@ mod tests {
         assert_eq!(result.unwrap(), content);
     }
 
+    #[test]
+    fn test_find_executable() {
+        let adapter = FileAdapter::new();
+
+        assert!(adapter.find_executable("sh").is_some());
+        assert!(adapter.find_executable("/bin/sh").is_some());
+        assert!(adapter.find_executable("wzb-no-such-program").is_none());
+    }
+
     #[test]
     fn test_error_handling() {
         let adapter = FileAdapter::new();
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::fs::remove_file;
+use std::io::{self, IsTerminal, Write};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use log::info;
+
+use crate::adapters::escapes::{osc_notify, osc_user_var};
+use crate::adapters::pane_job::{script_paths, PaneJob};
+use crate::domain::models::{Direction, Invocation, Signal, Step, TerminalError};
+use crate::ports::TerminalPort;
+
+/// Prefix of the pane identifiers reported for the invoking terminal
+const LOCAL_PANE: &str = "local";
+
+/// Runs tasks in the foreground of the invoking terminal, for
+/// environments without a terminal multiplexer.
+pub struct LocalAdapter {
+    /// Tasks running in parallel each get their own pane id and temp files
+    panes: AtomicUsize,
+    /// Process id of the step running for each pane, `None` once signalled
+    running: Mutex<BTreeMap<String, Option<u32>>>,
+}
+
+impl LocalAdapter {
+    pub fn new() -> Self {
+        Self {
+            panes: AtomicUsize::new(0),
+            running: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn run_step(&self, pane_id: &str, invocation: &Invocation, step: &Step) -> Result<ExitStatus> {
+        let argv = step.written_argv();
+        let mut command = Command::new(&argv[0]);
+        command.args(&argv[1..]).envs(&invocation.env);
+        if let Some(cwd) = &invocation.cwd {
+            command.current_dir(cwd);
+        }
+
+        let failed =
+            || TerminalError::PipeText(format!("Failed to run {} in the foreground", step.program));
+        let mut child = command.spawn().with_context(failed)?;
+        self.running
+            .lock()
+            .unwrap()
+            .insert(pane_id.to_string(), Some(child.id()));
+        child.wait().with_context(failed)
+    }
+
+    /// A signalled task runs no further steps
+    fn signalled(&self, pane_id: &str) -> bool {
+        matches!(self.running.lock().unwrap().get(pane_id), Some(None))
+    }
+}
+
+impl TerminalPort for LocalAdapter {
+    fn open_pane(&self, direction: Direction, _size: i32) -> Result<String> {
+        info!("Local backend ignores pane direction: {}", direction);
+        let pane = self.panes.fetch_add(1, Ordering::Relaxed) + 1;
+        Ok(format!("{}-{}", LOCAL_PANE, pane))
+    }
+
+    fn close_pane(&self, _pane_id: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn pipe_text_to_pane(&self, mut invocation: Invocation, pane_id: &str) -> Result<ExitStatus> {
+        if invocation.steps.is_empty() {
+            return Err(
+                TerminalError::PipeText(format!("No command to run in pane {}", pane_id)).into(),
+            );
+        }
+
+        // Run every step like `PaneJob` does, keeping the first failure
+        let job = PaneJob::new(pane_id);
+        job.prepare(&mut invocation)?;
+        if let Some(banner) = &invocation.banner {
+            println!("{}", banner);
+        }
+        let count = invocation.steps.len();
+        let mut first_failure = None;
+        let mut status = Ok(ExitStatus::from_raw(0));
+        for (index, step) in invocation.steps.iter().enumerate() {
+            if self.signalled(pane_id) {
+                break;
+            }
+            if count > 1 {
+                println!("{}", step.header(index + 1, count));
+            }
+            status = self.run_step(pane_id, &invocation, step);
+            match &status {
+                Ok(status) if status.success() => {}
+                Ok(failed) => {
+                    first_failure.get_or_insert(*failed);
+                    if !step.continue_on_error {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+
+        for path in script_paths(&invocation) {
+            let _ = remove_file(path);
+        }
+        self.running.lock().unwrap().remove(pane_id);
+        Ok(first_failure.unwrap_or(status?))
+    }
+
+    fn signal_task(&self, pane_id: &str, signal: Signal) -> Result<()> {
+        let mut running = self.running.lock().unwrap();
+        let pid = running.get(pane_id).copied().flatten();
+        running.insert(pane_id.to_string(), None);
+        let pid = pid
+            .ok_or_else(|| TerminalError::Signal(format!("No step running in pane {}", pane_id)))?;
+
+        info!("Send SIG{} to process {}", signal.name(), pid);
+        let status = Command::new("kill")
+            .args([format!("-{}", signal.name()), pid.to_string()])
+            .status()
+            .with_context(|| TerminalError::Signal(format!("Failed to run kill {}", pid)))?;
+        if !status.success() {
+            return Err(TerminalError::Signal(format!("Process {} is gone", pid)).into());
+        }
+        Ok(())
+    }
+
+    fn set_pane_title(&self, _pane_id: &str, _title: &str) -> Result<()> {
+        // Like its tab, the invoking terminal's title belongs to the user and
+        // would keep the task's once `wzb` exits
+        Ok(())
+    }
+
+    fn set_tab_title(&self, _pane_id: &str, _title: &str) -> Result<()> {
+        // The invoking terminal's tab belongs to the user
+        Ok(())
+    }
+
+    fn notify(&self, _pane_id: &str, title: &str, body: &str) -> Result<()> {
+        write_escape(&osc_notify(title, body))
+            .with_context(|| TerminalError::Notify(title.to_string()))
+    }
+
+    fn set_user_var(&self, _pane_id: &str, name: &str, value: &str) -> Result<()> {
+        write_escape(&osc_user_var(name, value))
+            .with_context(|| TerminalError::UserVar(name.to_string()))
+    }
+}
+
+/// Escapes are only meaningful when stdout is a terminal
+fn write_escape(sequence: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    if stdout.is_terminal() {
+        stdout.write_all(sequence.as_bytes())?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
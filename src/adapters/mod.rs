@@ -1,4 +1,16 @@
+pub mod backend_registry;
 pub mod cli_adapter;
+pub mod completions;
 pub mod config_adapter;
+pub mod diagnostics_adapter;
+pub mod environment_adapter;
+pub mod escapes;
 pub mod file_adapter;
+pub mod local_adapter;
+pub mod pane_job;
+pub mod paths;
+pub mod prompt_adapter;
+pub mod state_adapter;
 pub mod terminal_adapter;
+pub mod tmux_adapter;
+pub mod wezterm_export;
@@ -0,0 +1,620 @@
+use std::env;
+use std::fs::{read_dir, read_to_string, remove_file, symlink_metadata, write, DirBuilder};
+use std::io::{self, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{self, ExitStatus, Stdio};
+use std::thread::sleep;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use crate::domain::models::{Invocation, Signal, Step, TerminalError};
+
+/// How often the status file is polled while a task runs
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Polls between checks that the pane still exists
+const ALIVE_CHECK_EVERY: u32 = 10;
+
+/// A command typed into a multiplexer pane which reports its exit status
+/// through a file once it finishes.
+pub struct PaneJob {
+    pane_id: String,
+    /// Holds the job's files, only this user can use it
+    dir: PathBuf,
+    /// Start of the names of the job's files, `wzb-<pid>-<pane>`
+    name: String,
+    status_file: PathBuf,
+}
+
+impl PaneJob {
+    pub fn new(pane_id: &str) -> Self {
+        let name = format!("wzb-{}-{}", process::id(), pane_id.trim_start_matches('%'));
+        let dir = env::temp_dir().join(format!("wzb-{}", current_uid()));
+        Self {
+            pane_id: pane_id.to_string(),
+            status_file: dir.join(format!("{}.status", name)),
+            dir,
+            name,
+        }
+    }
+
+    /// Shell line running the invocation under `sh`, independent of the pane's shell.
+    /// The job has to be prepared with `prepare` first.
+    pub fn command_line(&self, invocation: &Invocation) -> String {
+        let mut command = match invocation.steps.as_slice() {
+            [step] => self.program_line(invocation, step),
+            steps => self.steps_script(invocation, steps),
+        };
+        if let Some(cwd) = &invocation.cwd {
+            command = format!("cd {} && {}", shell_quote(cwd), command);
+        }
+        if let Some(banner) = &invocation.banner {
+            command = format!("echo {}; {}", shell_quote(banner), command);
+        }
+        let mut script = format!(
+            "{}; echo $? > {}",
+            command,
+            shell_quote(&self.status_file.to_string_lossy())
+        );
+        let mut temp_files = self.script_files(invocation);
+        if invocation.timeout.is_some() {
+            // Leave the pid to find the task by
+            let pid_file = shell_quote(&self.pid_file().to_string_lossy());
+            script = format!("echo $$ > {}; {}", pid_file, script);
+            temp_files.push(pid_file);
+        }
+        // Outlive the signals sent to the task, by a timeout or Ctrl-C in the
+        // pane, to report its status, skipping the remaining steps
+        script = format!("trap run=0 INT TERM; {}", script);
+        if !temp_files.is_empty() {
+            script = format!("{}; rm -f {}", script, temp_files.join(" "));
+        }
+        format!("sh -c {}", shell_quote(&script))
+    }
+
+    /// Create the job's private directory, and write the body of each script
+    /// step to a new file in it, which the step runs from
+    pub fn prepare(&self, invocation: &mut Invocation) -> Result<()> {
+        private_dir(&self.dir).with_context(|| {
+            TerminalError::PipeText(format!("Failed to prepare a job for pane {}", self.pane_id))
+        })?;
+        for step in &mut invocation.steps {
+            if let Some(script) = &mut step.script {
+                let failed = || TerminalError::PipeText("Failed to write script".to_string());
+                let mut file = tempfile::Builder::new()
+                    .prefix(&format!("{}.", self.name))
+                    .suffix(".script")
+                    .tempfile_in(&self.dir)
+                    .with_context(failed)?;
+                file.write_all(script.body.as_bytes())
+                    .with_context(failed)?;
+                let path = file.into_temp_path().keep().with_context(failed)?;
+                script.path = Some(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Send `signal` to the job's process group, which interactive shells start
+    /// it in, or else to the children of the job's shell. The shell itself
+    /// can't trap SIGKILL, its status is reported and its files removed for it.
+    pub fn signal(&self, signal: Signal) -> Result<()> {
+        let pid = self.read_pid()?;
+        let group = process::Command::new("kill")
+            .args(["-s", signal.name(), "--", &format!("-{}", pid)])
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if !group {
+            // The shell traps all but SIGKILL, skipping the remaining steps
+            if signal != Signal::Kill {
+                let _ = process::Command::new("kill")
+                    .args(["-s", signal.name(), &pid.to_string()])
+                    .status();
+            }
+            let children = process::Command::new("pkill")
+                .args([&format!("-{}", signal.name()), "-P", &pid.to_string()])
+                .status()
+                .is_ok_and(|status| status.success());
+            if !children {
+                return Err(TerminalError::Signal(format!(
+                    "No process of pane {} took SIG{}",
+                    self.pane_id,
+                    signal.name()
+                ))
+                .into());
+            }
+        }
+
+        if group && signal == Signal::Kill {
+            let _ = remove_file(self.pid_file());
+            self.remove_scripts();
+            write(&self.status_file, format!("{}\n", 128 + signal.number())).with_context(
+                || TerminalError::Signal(self.status_file.to_string_lossy().to_string()),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Process id of the shell running the job, written when it may be signalled
+    fn pid_file(&self) -> PathBuf {
+        self.dir.join(format!("{}.pid", self.name))
+    }
+
+    /// The pid the job's shell wrote, from a private directory and a file of
+    /// this user only, so no one else can pick the processes signalled
+    fn read_pid(&self) -> Result<u32> {
+        let no_task = || TerminalError::Signal(format!("No task running in pane {}", self.pane_id));
+        private_dir(&self.dir).with_context(no_task)?;
+        let pid_file = self.pid_file();
+        let metadata = symlink_metadata(&pid_file).with_context(no_task)?;
+        if !metadata.is_file() || metadata.uid() != current_uid() {
+            return Err(TerminalError::Signal(format!(
+                "Refusing to read {}, which is not a file of this user",
+                pid_file.to_string_lossy()
+            ))
+            .into());
+        }
+        read_to_string(&pid_file)
+            .ok()
+            .and_then(|content| content.trim().parse::<u32>().ok())
+            .ok_or_else(|| no_task().into())
+    }
+
+    /// Remove the job's scripts, which its shell removes when it gets to
+    fn remove_scripts(&self) {
+        let prefix = format!("{}.", self.name);
+        let Ok(entries) = read_dir(&self.dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with(&prefix) && name.ends_with(".script") {
+                let _ = remove_file(entry.path());
+            }
+        }
+    }
+
+    /// Quoted paths of the invocation's script files
+    fn script_files(&self, invocation: &Invocation) -> Vec<String> {
+        script_paths(invocation)
+            .map(|path| shell_quote(&path.to_string_lossy()))
+            .collect()
+    }
+
+    /// A step's program and args, under the invocation's environment
+    fn program_line(&self, invocation: &Invocation, step: &Step) -> String {
+        let command = step
+            .written_argv()
+            .iter()
+            .map(|arg| shell_quote(arg))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if invocation.env.is_empty() {
+            return command;
+        }
+        let assignments = invocation
+            .env
+            .iter()
+            .map(|(name, value)| shell_quote(&format!("{}={}", name, value)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!("env {} {}", assignments, command)
+    }
+
+    /// Steps under a header each, stopping at the first failure unless the step
+    /// continues on error. Exits with the status of the first failing step.
+    fn steps_script(&self, invocation: &Invocation, steps: &[Step]) -> String {
+        let mut script = vec!["s=0".to_string(), "run=1".to_string()];
+        for (index, step) in steps.iter().enumerate() {
+            let stop = if step.continue_on_error {
+                ""
+            } else {
+                " [ $c -eq 0 ] || run=0;"
+            };
+            script.push(format!(
+                "if [ $run -eq 1 ]; then echo {}; {}; c=$?; [ $s -ne 0 ] || s=$c;{} fi",
+                shell_quote(&step.header(index + 1, steps.len())),
+                self.program_line(invocation, step),
+                stop
+            ));
+        }
+        script.push("(exit $s)".to_string());
+        format!("{{ {}; }}", script.join("; "))
+    }
+
+    /// Block until the job reports its status or the pane goes away
+    pub fn wait(&self, pane_alive: impl Fn() -> bool) -> Result<ExitStatus> {
+        for poll in 0.. {
+            if let Ok(content) = read_to_string(&self.status_file) {
+                if let Ok(code) = content.trim().parse::<i32>() {
+                    let _ = remove_file(&self.status_file);
+                    return Ok(ExitStatus::from_raw(code << 8));
+                }
+            }
+
+            if poll % ALIVE_CHECK_EVERY == 0 && !pane_alive() {
+                return Err(TerminalError::PipeText(format!(
+                    "Pane {} closed before the task finished",
+                    self.pane_id
+                )))
+                .context("Failed to wait for task");
+            }
+
+            sleep(POLL_INTERVAL);
+        }
+        unreachable!("polling never stops on its own")
+    }
+}
+
+/// Create `dir` for this user only, or check that an existing `dir` is a
+/// directory no one else can use
+fn private_dir(dir: &Path) -> io::Result<()> {
+    match DirBuilder::new().mode(0o700).create(dir) {
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+        created => return created,
+    }
+    let metadata = symlink_metadata(dir)?;
+    if !metadata.is_dir() || metadata.uid() != current_uid() || metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is not a directory private to this user", dir.display()),
+        ));
+    }
+    Ok(())
+}
+
+fn current_uid() -> u32 {
+    // SAFETY: geteuid has no preconditions and always succeeds
+    unsafe { libc::geteuid() }
+}
+
+/// Temp files written by `PaneJob::prepare`
+pub fn script_paths(invocation: &Invocation) -> impl Iterator<Item = &Path> {
+    invocation
+        .steps
+        .iter()
+        .filter_map(|step| step.script.as_ref()?.path.as_deref())
+}
+
+/// Quote a word for POSIX shells, leaving plain words untouched
+pub fn shell_quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./:=@%+,".contains(c));
+
+    if plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::fs::{metadata, set_permissions, write, Permissions};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+
+    fn step(line: &str) -> Step {
+        let mut words = line.split_whitespace().map(String::from);
+        Step {
+            program: words.next().unwrap(),
+            args: words.collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Run the job's line like a pane would and wait for its status
+    fn run(job: &PaneJob, invocation: &Invocation) -> (String, Option<i32>) {
+        private_dir(&job.dir).unwrap();
+        let line = job.command_line(invocation);
+        let script = line.strip_prefix("sh -c ").unwrap();
+        let output = Command::new("sh")
+            .args(["-c", &format!("eval {}", script), "sh"])
+            .output()
+            .unwrap();
+        let status = job.wait(|| true).unwrap();
+        (
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            status.code(),
+        )
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("build"), "build");
+        assert_eq!(shell_quote("run build"), "'run build'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn test_command_line_reports_status() {
+        let job = PaneJob::new("%7");
+        let line = job.command_line(&Invocation {
+            steps: vec![step("cargo test")],
+            ..Default::default()
+        });
+
+        assert!(line.starts_with("sh -c 'trap run=0 INT TERM; cargo test; echo $? > "));
+        assert!(line.contains(".status"));
+    }
+
+    #[test]
+    fn test_command_line_changes_directory() {
+        let job = PaneJob::new("%8");
+        let line = job.command_line(&Invocation {
+            steps: vec![step("make")],
+            cwd: Some("/work/my app".to_string()),
+            ..Default::default()
+        });
+
+        assert!(line.starts_with(
+            r"sh -c 'trap run=0 INT TERM; cd '\''/work/my app'\'' && make; echo $? > "
+        ));
+    }
+
+    #[test]
+    fn test_command_line_sets_environment() {
+        let job = PaneJob::new("%9");
+        let line = job.command_line(&Invocation {
+            steps: vec![step("make")],
+            env: BTreeMap::from([("MODE".to_string(), "release build".to_string())]),
+            ..Default::default()
+        });
+
+        assert!(line.starts_with(
+            r"sh -c 'trap run=0 INT TERM; env '\''MODE=release build'\'' make; echo $? > "
+        ));
+    }
+
+    #[test]
+    fn test_banner_comes_first() {
+        let job = PaneJob::new("test-banner");
+        let invocation = Invocation {
+            steps: vec![step("echo one")],
+            banner: Some("↻ attempt 2/3 after exit 1".to_string()),
+            ..Default::default()
+        };
+
+        let (output, code) = run(&job, &invocation);
+        assert_eq!(output, "↻ attempt 2/3 after exit 1\none\n");
+        assert_eq!(code, Some(0));
+    }
+
+    #[test]
+    fn test_wait_reads_exit_code() {
+        let job = PaneJob::new("test-wait");
+        private_dir(&job.dir).unwrap();
+        write(&job.status_file, "101\n").unwrap();
+
+        let status = job.wait(|| true).unwrap();
+        assert_eq!(status.code(), Some(101));
+        assert!(!job.status_file.exists());
+    }
+
+    #[test]
+    fn test_wait_fails_when_pane_closes() {
+        let job = PaneJob::new("test-closed");
+        assert!(job.wait(|| false).is_err());
+    }
+
+    #[test]
+    fn test_steps_stop_at_the_first_failure() {
+        let job = PaneJob::new("test-steps-stop");
+        let invocation = Invocation {
+            steps: vec![
+                step("echo one"),
+                Step {
+                    args: vec!["-c".to_string(), "exit 3".to_string()],
+                    ..step("sh")
+                },
+                step("echo three"),
+            ],
+            ..Default::default()
+        };
+
+        let (output, code) = run(&job, &invocation);
+        assert_eq!(output, "▶ [1/3] echo one\none\n▶ [2/3] sh -c exit 3\n");
+        assert_eq!(code, Some(3));
+    }
+
+    #[test]
+    fn test_steps_continue_on_error_keep_first_failure() {
+        let job = PaneJob::new("test-steps-continue");
+        let invocation = Invocation {
+            steps: vec![
+                Step {
+                    continue_on_error: true,
+                    ..step("false")
+                },
+                step("echo two"),
+                Step {
+                    args: vec!["-c".to_string(), "exit 5".to_string()],
+                    ..step("sh")
+                },
+            ],
+            ..Default::default()
+        };
+
+        let (output, code) = run(&job, &invocation);
+        assert!(
+            output.ends_with("two\n▶ [3/3] sh -c exit 5\n"),
+            "{}",
+            output
+        );
+        assert_eq!(code, Some(1));
+    }
+
+    #[test]
+    fn test_scripts_run_from_temp_files() {
+        let job = PaneJob::new("test-script");
+        let command = crate::domain::models::Command {
+            script: Some("echo \"hello $1\"\nexit 4\n".to_string()),
+            args: vec!["you".to_string()],
+            ..Default::default()
+        };
+        let mut invocation = Invocation {
+            steps: command.to_steps(),
+            ..Default::default()
+        };
+
+        job.prepare(&mut invocation).unwrap();
+        let path = script_paths(&invocation).next().unwrap().to_path_buf();
+        let mode = metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let (output, code) = run(&job, &invocation);
+        assert_eq!(output, "hello you\n");
+        assert_eq!(code, Some(4));
+        assert!(!path.exists());
+    }
+
+    /// Start the job's line in the background like a pane would, until its first step runs
+    fn start(job: &PaneJob, invocation: &Invocation, own_group: bool) -> process::Child {
+        private_dir(&job.dir).unwrap();
+        let line = job.command_line(invocation);
+        let script = line.strip_prefix("sh -c ").unwrap();
+        let mut command = Command::new("sh");
+        command
+            .args(["-c", &format!("eval {}", script), "sh"])
+            .stdout(Stdio::piped());
+        if own_group {
+            command.process_group(0);
+        }
+        let child = command.spawn().unwrap();
+        // `eval` runs the job in the shell itself
+        let started = || {
+            Command::new("pgrep")
+                .args(["-P", &child.id().to_string()])
+                .stdout(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success())
+        };
+        while !started() {
+            sleep(Duration::from_millis(10));
+        }
+        child
+    }
+
+    #[test]
+    fn test_interrupt_skips_remaining_steps() {
+        let job = PaneJob::new("test-interrupt");
+        let invocation = Invocation {
+            steps: vec![
+                Step {
+                    continue_on_error: true,
+                    ..step("sleep 5")
+                },
+                step("echo after"),
+            ],
+            timeout: Some(Duration::from_secs(1)),
+            ..Default::default()
+        };
+
+        // Without a process group of its own only the shell's children are signalled
+        let child = start(&job, &invocation, false);
+        job.signal(Signal::Interrupt).unwrap();
+        let output = child.wait_with_output().unwrap();
+
+        assert_eq!(job.wait(|| true).unwrap().code(), Some(130));
+        assert!(!String::from_utf8_lossy(&output.stdout).contains("after"));
+        assert!(!job.pid_file().exists());
+    }
+
+    #[test]
+    fn test_ctrl_c_reports_status_without_timeout() {
+        let job = PaneJob::new("test-ctrl-c");
+        let invocation = Invocation {
+            steps: vec![step("sleep 5"), step("echo after")],
+            ..Default::default()
+        };
+
+        // Ctrl-C interrupts the pane's foreground process group
+        let child = start(&job, &invocation, true);
+        Command::new("kill")
+            .args(["-s", "INT", "--", &format!("-{}", child.id())])
+            .status()
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+
+        assert_eq!(job.wait(|| true).unwrap().code(), Some(130));
+        assert!(!String::from_utf8_lossy(&output.stdout).contains("after"));
+    }
+
+    #[test]
+    fn test_kill_reports_status_for_the_shell() {
+        let job = PaneJob::new("test-kill");
+        let invocation = Invocation {
+            steps: vec![step("sleep 5")],
+            timeout: Some(Duration::from_secs(1)),
+            ..Default::default()
+        };
+
+        let mut child = start(&job, &invocation, true);
+        job.signal(Signal::Kill).unwrap();
+        child.wait().unwrap();
+
+        assert_eq!(job.wait(|| true).unwrap().code(), Some(137));
+        assert!(job.signal(Signal::Kill).is_err());
+    }
+
+    #[test]
+    fn test_kill_removes_scripts() {
+        let job = PaneJob::new("test-kill-scripts");
+        let command = crate::domain::models::Command {
+            script: Some("sleep 5\n".to_string()),
+            ..Default::default()
+        };
+        let mut invocation = Invocation {
+            steps: command.to_steps(),
+            timeout: Some(Duration::from_secs(1)),
+            ..Default::default()
+        };
+        job.prepare(&mut invocation).unwrap();
+        let path = script_paths(&invocation).next().unwrap().to_path_buf();
+
+        let mut child = start(&job, &invocation, true);
+        job.signal(Signal::Kill).unwrap();
+        child.wait().unwrap();
+
+        assert_eq!(job.wait(|| true).unwrap().code(), Some(137));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_signal_refuses_pid_files_it_did_not_write() {
+        let job = PaneJob::new("test-planted");
+        private_dir(&job.dir).unwrap();
+        let planted = env::temp_dir().join("wzb-test-planted.pid");
+        write(&planted, "1\n").unwrap();
+        let _ = remove_file(job.pid_file());
+        std::os::unix::fs::symlink(&planted, job.pid_file()).unwrap();
+
+        let err = job.signal(Signal::Kill).unwrap_err();
+        assert!(err.to_string().contains("Refusing to read"), "{}", err);
+        remove_file(job.pid_file()).unwrap();
+        remove_file(planted).unwrap();
+    }
+
+    #[test]
+    fn test_private_dir_refuses_shared_directories() {
+        let shared = tempfile::tempdir().unwrap();
+        set_permissions(shared.path(), Permissions::from_mode(0o777)).unwrap();
+        assert!(private_dir(shared.path()).is_err());
+
+        let dir = shared.path().join("private");
+        private_dir(&dir).unwrap();
+        assert_eq!(metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+        private_dir(&dir).unwrap();
+    }
+}
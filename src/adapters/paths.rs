@@ -0,0 +1,76 @@
+use std::env;
+use std::path::{Component, Path, PathBuf};
+
+use crate::constants::{CONFIG_FILE, DOTDIR, STATE_DIR};
+
+/// Walk up from `start` to the nearest directory holding a wez bits config
+pub fn find_project_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| dir.join(DOTDIR).join(CONFIG_FILE).is_file())
+        .map(Path::to_path_buf)
+}
+
+/// Per project state directory, `$XDG_STATE_HOME/wez-bits/<project>`
+pub fn state_dir(project_root: &Path) -> Option<PathBuf> {
+    let base = env::var_os("XDG_STATE_HOME")
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))?;
+
+    Some(base.join(STATE_DIR).join(project_key(project_root)))
+}
+
+/// Flatten a project path into a single directory name
+fn project_key(project_root: &Path) -> String {
+    let key = project_root
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("-");
+
+    if key.is_empty() {
+        "root".to_string()
+    } else {
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, write};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_find_project_root_from_subdirectory() {
+        let temp_dir = tempdir().unwrap();
+        let nested = temp_dir.path().join("src/deeply/nested");
+        create_dir_all(&nested).unwrap();
+        create_dir_all(temp_dir.path().join(DOTDIR)).unwrap();
+        write(temp_dir.path().join(DOTDIR).join(CONFIG_FILE), "").unwrap();
+
+        assert_eq!(
+            find_project_root(&nested),
+            Some(temp_dir.path().to_path_buf())
+        );
+    }
+
+    #[test]
+    fn test_find_project_root_without_config() {
+        let temp_dir = tempdir().unwrap();
+        assert_eq!(find_project_root(temp_dir.path()), None);
+    }
+
+    #[test]
+    fn test_project_key() {
+        assert_eq!(
+            project_key(Path::new("/home/me/project")),
+            "home-me-project"
+        );
+        assert_eq!(project_key(Path::new("/")), "root");
+    }
+}
@@ -0,0 +1,213 @@
+use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::io::{self, IsTerminal, Read, Write};
+use std::process::{Command, Stdio};
+
+use anyhow::{anyhow, Context, Result};
+use log::info;
+
+use crate::domain::models::Key;
+use crate::ports::PromptPort;
+
+/// The controlling terminal, even when stdin or stdout are redirected
+const TTY: &str = "/dev/tty";
+
+/// Size assumed when the terminal does not report one
+const DEFAULT_SIZE: (usize, usize) = (24, 80);
+
+/// Reads single key presses from the controlling terminal in raw mode and
+/// draws on its alternate screen.
+pub struct PromptAdapter {
+    tty: RefCell<Option<File>>,
+    saved_mode: RefCell<Option<String>>,
+}
+
+impl PromptAdapter {
+    pub fn new() -> Self {
+        Self {
+            tty: RefCell::new(None),
+            saved_mode: RefCell::new(None),
+        }
+    }
+
+    /// Next byte, `None` when nothing arrived within the read timeout
+    fn read_byte(&self) -> Result<Option<u8>> {
+        let mut tty = self.tty.borrow_mut();
+        let tty = tty
+            .as_mut()
+            .ok_or_else(|| anyhow!("The prompt has not been started"))?;
+
+        let mut byte = [0u8];
+        match tty.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    fn write(&self, text: &str) -> Result<()> {
+        let mut tty = self.tty.borrow_mut();
+        let tty = tty
+            .as_mut()
+            .ok_or_else(|| anyhow!("The prompt has not been started"))?;
+        tty.write_all(text.as_bytes())?;
+        tty.flush()?;
+        Ok(())
+    }
+}
+
+impl PromptPort for PromptAdapter {
+    fn is_interactive(&self) -> bool {
+        io::stdin().is_terminal()
+    }
+
+    fn start(&self) -> Result<()> {
+        info!("Switch {} to raw mode", TTY);
+        let saved = stty(&["-g"]).context("Failed to read the terminal mode")?;
+        // Reads give up after a tenth of a second, telling Esc from escape sequences
+        stty(&["raw", "-echo", "min", "0", "time", "1"])
+            .context("Failed to switch the terminal to raw mode")?;
+        *self.saved_mode.borrow_mut() = Some(saved);
+
+        let tty = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(TTY)
+            .with_context(|| format!("Failed to open {}", TTY))?;
+        *self.tty.borrow_mut() = Some(tty);
+
+        // Alternate screen with a hidden cursor
+        self.write("\x1b[?1049h\x1b[?25l")
+    }
+
+    fn read_key(&self) -> Result<Key> {
+        loop {
+            let Some(first) = self.read_byte()? else {
+                continue;
+            };
+            if let Some(key) = decode_key(first, || self.read_byte().ok().flatten()) {
+                return Ok(key);
+            }
+        }
+    }
+
+    fn draw(&self, lines: &[String]) -> Result<()> {
+        self.write(&format!("\x1b[H\x1b[2J{}", lines.join("\r\n")))
+    }
+
+    fn size(&self) -> (usize, usize) {
+        stty(&["size"])
+            .ok()
+            .and_then(|size| {
+                let (rows, columns) = size.split_once(' ')?;
+                Some((rows.parse().ok()?, columns.parse().ok()?))
+            })
+            .filter(|&(rows, columns)| rows > 0 && columns > 0)
+            .unwrap_or(DEFAULT_SIZE)
+    }
+
+    fn finish(&self) -> Result<()> {
+        if self.tty.borrow().is_some() {
+            self.write("\x1b[?25h\x1b[?1049l")?;
+        }
+        self.tty.borrow_mut().take();
+
+        match self.saved_mode.borrow_mut().take() {
+            Some(saved) => stty(&[&saved])
+                .map(|_| ())
+                .context("Failed to restore the terminal mode"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Run `stty` against the controlling terminal
+fn stty(args: &[&str]) -> Result<String> {
+    let tty = File::open(TTY).with_context(|| format!("Failed to open {}", TTY))?;
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(tty)
+        .stderr(Stdio::null())
+        .output()
+        .context("Failed to run stty")?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(anyhow!("stty {} failed", args.join(" ")))
+    }
+}
+
+/// Decode the key starting with `first`, pulling the rest of a sequence
+/// from `next`. Unsupported keys decode to `None`.
+fn decode_key(first: u8, mut next: impl FnMut() -> Option<u8>) -> Option<Key> {
+    match first {
+        b'\r' | b'\n' => Some(Key::Enter),
+        0x7f | 0x08 => Some(Key::Backspace),
+        0x1b => match next() {
+            None => Some(Key::Esc),
+            Some(b'\r') => Some(Key::AltEnter),
+            Some(b'[') | Some(b'O') => {
+                // CSI and SS3 sequences end with a byte in `@`..`~`
+                let mut last = next()?;
+                while !(0x40..=0x7e).contains(&last) {
+                    last = next()?;
+                }
+                match last {
+                    b'A' => Some(Key::Up),
+                    b'B' => Some(Key::Down),
+                    _ => None,
+                }
+            }
+            Some(_) => None,
+        },
+        0x01..=0x1a => Some(Key::Ctrl((b'a' + first - 1) as char)),
+        _ if first < 0x20 => None,
+        _ => {
+            let width = match first {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            let mut bytes = vec![first];
+            for _ in 1..width {
+                bytes.push(next()?);
+            }
+            std::str::from_utf8(&bytes)
+                .ok()?
+                .chars()
+                .next()
+                .map(Key::Char)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(bytes: &[u8]) -> Option<Key> {
+        let mut rest = bytes[1..].iter().copied();
+        decode_key(bytes[0], || rest.next())
+    }
+
+    #[test]
+    fn test_decode_plain_and_control_keys() {
+        assert_eq!(decode(b"a"), Some(Key::Char('a')));
+        assert_eq!(decode("é".as_bytes()), Some(Key::Char('é')));
+        assert_eq!(decode(b"\r"), Some(Key::Enter));
+        assert_eq!(decode(b"\x7f"), Some(Key::Backspace));
+        assert_eq!(decode(b"\x04"), Some(Key::Ctrl('d')));
+        assert_eq!(decode(b"\x14"), Some(Key::Ctrl('t')));
+    }
+
+    #[test]
+    fn test_decode_escape_sequences() {
+        assert_eq!(decode(b"\x1b"), Some(Key::Esc));
+        assert_eq!(decode(b"\x1b\r"), Some(Key::AltEnter));
+        assert_eq!(decode(b"\x1b[A"), Some(Key::Up));
+        assert_eq!(decode(b"\x1bOB"), Some(Key::Down));
+        // Unsupported sequences are swallowed whole
+        assert_eq!(decode(b"\x1b[1;5C"), None);
+    }
+}
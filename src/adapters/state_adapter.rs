@@ -0,0 +1,237 @@
+use std::fs::{create_dir_all, read_dir, read_to_string, rename, write, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+
+use crate::domain::models::{FileSystemError, HistoryEntry, RunStatus, TaskRun};
+use crate::ports::StatePort;
+
+/// Every finished run, one JSON object per line
+const HISTORY_FILE: &str = "history.jsonl";
+
+/// Stores the latest run of each task as `<state dir>/runs/<task>.json`
+/// and every finished run as a line of `<state dir>/history.jsonl`
+pub struct StateAdapter {
+    state_dir: Option<PathBuf>,
+}
+
+impl StateAdapter {
+    pub fn new(state_dir: Option<PathBuf>) -> Self {
+        Self { state_dir }
+    }
+
+    fn state_path(&self, name: &str) -> Result<PathBuf> {
+        self.state_dir
+            .as_ref()
+            .map(|dir| dir.join(name))
+            .ok_or_else(|| anyhow!("Neither XDG_STATE_HOME nor HOME is set"))
+    }
+
+    fn runs_dir(&self) -> Result<PathBuf> {
+        self.state_path("runs")
+    }
+}
+
+impl StatePort for StateAdapter {
+    fn record_run(&self, run: &TaskRun) -> Result<()> {
+        let dir = self.runs_dir()?;
+        create_dir_all(&dir)
+            .with_context(|| FileSystemError::CreateDirectory(dir.display().to_string()))?;
+
+        // Write then rename so readers never see a partial file
+        let path = dir.join(format!("{}.json", run.name.replace('/', "_")));
+        let partial = path.with_extension(format!("json.{}", run.pid));
+        info!("Recording {} run of {}", run.status, run.name);
+        write(&partial, serde_json::to_string(run)?)
+            .and_then(|_| rename(&partial, &path))
+            .with_context(|| FileSystemError::Write(path.display().to_string()))
+    }
+
+    fn load_runs(&self) -> Result<Vec<TaskRun>> {
+        let dir = self.runs_dir()?;
+        if !dir.is_dir() {
+            return Ok(vec![]);
+        }
+
+        let mut runs = read_dir(&dir)
+            .with_context(|| FileSystemError::Read(dir.display().to_string()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(
+                |path| match read_to_string(&path).map(|c| serde_json::from_str(&c)) {
+                    Ok(Ok(run)) => Some(run),
+                    _ => {
+                        warn!("Ignoring unreadable task state {}", path.display());
+                        None
+                    }
+                },
+            )
+            .map(mark_abandoned)
+            .collect::<Vec<TaskRun>>();
+
+        runs.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(runs)
+    }
+
+    fn append_history(&self, entry: &HistoryEntry) -> Result<()> {
+        let path = self.state_path(HISTORY_FILE)?;
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)
+                .with_context(|| FileSystemError::CreateDirectory(dir.display().to_string()))?;
+        }
+
+        // A single append per line keeps parallel tasks from interleaving
+        let line = format!("{}\n", serde_json::to_string(entry)?);
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .with_context(|| FileSystemError::Write(path.display().to_string()))
+    }
+
+    fn load_history(&self) -> Result<Vec<HistoryEntry>> {
+        let path = self.state_path(HISTORY_FILE)?;
+        if !path.is_file() {
+            return Ok(vec![]);
+        }
+
+        let content = read_to_string(&path)
+            .with_context(|| FileSystemError::Read(path.display().to_string()))?;
+        Ok(content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(entry) => Some(entry),
+                Err(err) => {
+                    warn!("Ignoring unreadable history line: {}", err);
+                    None
+                }
+            })
+            .collect())
+    }
+}
+
+/// A run whose `wzb` process is gone can no longer report its result
+fn mark_abandoned(mut run: TaskRun) -> TaskRun {
+    if run.status == RunStatus::Running && !process_alive(run.pid) {
+        run.status = RunStatus::Failed;
+    }
+    run
+}
+
+fn process_alive(pid: u32) -> bool {
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::{Direction, TaskClose};
+    use std::collections::BTreeMap;
+    use std::process;
+    use tempfile::tempdir;
+
+    fn run(name: &str, status: RunStatus, pid: u32) -> TaskRun {
+        TaskRun {
+            name: name.to_string(),
+            status,
+            pane_id: "4".to_string(),
+            pid,
+            started_at: 1_700_000_000,
+            duration_ms: None,
+            exit_code: None,
+        }
+    }
+
+    #[test]
+    fn test_record_and_load_runs() {
+        let temp_dir = tempdir().unwrap();
+        let adapter = StateAdapter::new(Some(temp_dir.path().to_path_buf()));
+
+        let pid = process::id();
+        adapter
+            .record_run(&run("test", RunStatus::Running, pid))
+            .unwrap();
+        adapter
+            .record_run(&run("build", RunStatus::Success, pid))
+            .unwrap();
+        adapter
+            .record_run(&run("test", RunStatus::Failed, pid))
+            .unwrap();
+
+        let runs = adapter.load_runs().unwrap();
+        assert_eq!(
+            runs,
+            vec![
+                run("build", RunStatus::Success, pid),
+                run("test", RunStatus::Failed, pid)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_runs_marks_abandoned_runs() {
+        let temp_dir = tempdir().unwrap();
+        let adapter = StateAdapter::new(Some(temp_dir.path().to_path_buf()));
+
+        adapter
+            .record_run(&run("test", RunStatus::Running, u32::MAX / 2))
+            .unwrap();
+
+        let runs = adapter.load_runs().unwrap();
+        assert_eq!(runs[0].status, RunStatus::Failed);
+    }
+
+    #[test]
+    fn test_append_and_load_history() {
+        let temp_dir = tempdir().unwrap();
+        let adapter = StateAdapter::new(Some(temp_dir.path().join("project")));
+        assert!(adapter.load_history().unwrap().is_empty());
+
+        let entry = |name: &str, exit_code: Option<i32>| HistoryEntry {
+            name: name.to_string(),
+            command: "cargo test".to_string(),
+            cwd: None,
+            started_at: 1_700_000_000,
+            duration_ms: 1200,
+            exit_code,
+            timed_out: false,
+            attempt: None,
+            timeout: None,
+            pane_id: "4".to_string(),
+            close: TaskClose::OnSuccess,
+            direction: Direction::Right,
+            extra_args: vec!["--nocapture".to_string()],
+            params: BTreeMap::from([("env".to_string(), "dev".to_string())]),
+        };
+        adapter.append_history(&entry("test", Some(1))).unwrap();
+        adapter.append_history(&entry("build", None)).unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(temp_dir.path().join("project").join(HISTORY_FILE))
+            .unwrap()
+            .write_all(b"{broken\n")
+            .unwrap();
+
+        assert_eq!(
+            adapter.load_history().unwrap(),
+            vec![entry("test", Some(1)), entry("build", None)]
+        );
+    }
+
+    #[test]
+    fn test_load_runs_without_state() {
+        let temp_dir = tempdir().unwrap();
+        let adapter = StateAdapter::new(Some(temp_dir.path().join("missing")));
+        assert!(adapter.load_runs().unwrap().is_empty());
+    }
+}
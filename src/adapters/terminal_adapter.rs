@@ -1,9 +1,13 @@
+use std::fs::OpenOptions;
+use std::io::Write;
 use std::process::{Command, ExitStatus, Stdio};
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use log::info;
 
-use crate::domain::models::{Direction, TerminalError};
+use crate::adapters::escapes::{osc_notify, osc_title, osc_user_var};
+use crate::adapters::pane_job::PaneJob;
+use crate::domain::models::{Direction, Invocation, Signal, TerminalError};
 use crate::ports::TerminalPort;
 
 pub struct TerminalAdapter {}
@@ -29,7 +33,10 @@ impl TerminalPort for TerminalAdapter {
             .args(args)
             .output()
             .with_context(|| {
-                TerminalError::OpenPane("Failed to run wezterm command".to_string())
+                TerminalError::OpenPane(
+                    "Failed to run wezterm, is it installed and on PATH? Run `wzb doctor`"
+                        .to_string(),
+                )
             })?;
 
         let stdout = String::from_utf8(output.stdout).with_context(|| {
@@ -38,7 +45,12 @@ impl TerminalPort for TerminalAdapter {
 
         let pane_id = stdout.trim();
         if pane_id.is_empty() {
-            Err(TerminalError::OpenPane(format!("There is no pane {direction}")).into())
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            Err(TerminalError::OpenPane(format!(
+                "wezterm could not split the pane {direction}: {}. Run `wzb doctor` to diagnose",
+                stderr.trim()
+            ))
+            .into())
         } else {
             Ok(pane_id.to_string())
         }
@@ -54,28 +66,112 @@ impl TerminalPort for TerminalAdapter {
         Ok(())
     }
 
-    fn pipe_text_to_pane(&self, args: Vec<String>, pane_id: &str) -> Result<ExitStatus> {
-        let project_task = Command::new("echo")
-            .args(args)
-            .stdout(Stdio::piped())
-            .spawn()
-            .with_context(|| {
-                TerminalError::PipeText(format!(
-                    "Failed to create echo command for pane {}",
-                    pane_id
-                ))
-            })?;
+    fn pipe_text_to_pane(&self, mut invocation: Invocation, pane_id: &str) -> Result<ExitStatus> {
+        let job = PaneJob::new(pane_id);
+        job.prepare(&mut invocation)?;
 
-        let output = Command::new("wezterm")
+        let mut send_text = Command::new("wezterm")
             .args(["cli", "send-text", "--pane-id", pane_id, "--no-paste"])
-            .stdin(Stdio::from(project_task.stdout.unwrap()))
+            .stdin(Stdio::piped())
             .stdout(Stdio::inherit())
             .spawn()
-            .and_then(|c| c.wait_with_output())
             .with_context(|| {
                 TerminalError::PipeText(format!("Failed to pipe text to pane {}", pane_id))
             })?;
 
-        Ok(output.status)
+        if let Some(mut stdin) = send_text.stdin.take() {
+            writeln!(stdin, "{}", job.command_line(&invocation)).with_context(|| {
+                TerminalError::PipeText(format!("Failed to write text for pane {}", pane_id))
+            })?;
+        }
+
+        let sent = send_text.wait().with_context(|| {
+            TerminalError::PipeText(format!("Failed to pipe text to pane {}", pane_id))
+        })?;
+        if !sent.success() {
+            return Err(TerminalError::PipeText(format!(
+                "wezterm could not send text to pane {}",
+                pane_id
+            ))
+            .into());
+        }
+
+        job.wait(|| self.pane_exists(pane_id))
+    }
+
+    fn signal_task(&self, pane_id: &str, signal: Signal) -> Result<()> {
+        info!("Send SIG{} to the task in pane {}", signal.name(), pane_id);
+        PaneJob::new(pane_id).signal(signal)
+    }
+
+    fn set_pane_title(&self, pane_id: &str, title: &str) -> Result<()> {
+        info!("Set title of pane {}: {}", pane_id, title);
+        self.write_to_pane_tty(pane_id, &osc_title(title))
+            .with_context(|| TerminalError::Title(format!("Failed to title pane {}", pane_id)))
+    }
+
+    fn set_tab_title(&self, pane_id: &str, title: &str) -> Result<()> {
+        info!("Set title of tab holding pane {}: {}", pane_id, title);
+        let status = Command::new("wezterm")
+            .args(["cli", "set-tab-title", "--pane-id", pane_id, title])
+            .status()
+            .with_context(|| TerminalError::Title(format!("Failed to title tab of {}", pane_id)))?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(TerminalError::Title(format!("wezterm could not title tab of {}", pane_id)).into())
+        }
+    }
+
+    fn notify(&self, pane_id: &str, title: &str, body: &str) -> Result<()> {
+        info!("Notify from pane {}: {} {}", pane_id, title, body);
+        self.write_to_pane_tty(pane_id, &osc_notify(title, body))
+            .with_context(|| TerminalError::Notify(format!("Failed to notify from {}", pane_id)))
+    }
+
+    fn set_user_var(&self, pane_id: &str, name: &str, value: &str) -> Result<()> {
+        info!("Set user var on pane {}: {}={}", pane_id, name, value);
+        self.write_to_pane_tty(pane_id, &osc_user_var(name, value))
+            .with_context(|| {
+                TerminalError::UserVar(format!("Failed to set {} on {}", name, pane_id))
+            })
+    }
+}
+
+impl TerminalAdapter {
+    fn list_panes(&self) -> Result<Vec<serde_json::Value>> {
+        let output = Command::new("wezterm")
+            .args(["cli", "list", "--format", "json"])
+            .output()
+            .context("Failed to run wezterm cli list")?;
+
+        serde_json::from_slice(&output.stdout).context("Failed to parse wezterm cli list output")
+    }
+
+    fn find_pane(&self, pane_id: &str) -> Option<serde_json::Value> {
+        let pane_id = pane_id.parse::<u64>().ok()?;
+        self.list_panes()
+            .ok()?
+            .into_iter()
+            .find(|pane| pane["pane_id"].as_u64() == Some(pane_id))
+    }
+
+    fn pane_exists(&self, pane_id: &str) -> bool {
+        self.find_pane(pane_id).is_some()
+    }
+
+    /// Write raw escape sequences to the pane's terminal device
+    fn write_to_pane_tty(&self, pane_id: &str, sequence: &str) -> Result<()> {
+        let tty = self
+            .find_pane(pane_id)
+            .and_then(|pane| pane["tty_name"].as_str().map(str::to_string))
+            .ok_or_else(|| anyhow!("No tty found for pane {}", pane_id))?;
+
+        OpenOptions::new()
+            .write(true)
+            .open(&tty)
+            .and_then(|mut device| device.write_all(sequence.as_bytes()))
+            .with_context(|| format!("Failed to write to {}", tty))
     }
 }
@@ -0,0 +1,159 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::process::{Command, ExitStatus};
+
+use anyhow::{anyhow, Context, Result};
+use log::info;
+
+use crate::adapters::escapes::{osc9_notify, osc_user_var, tmux_passthrough};
+use crate::adapters::pane_job::PaneJob;
+use crate::domain::models::{Direction, Invocation, Signal, TerminalError};
+use crate::ports::TerminalPort;
+
+pub struct TmuxAdapter {}
+
+impl TmuxAdapter {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl TerminalPort for TmuxAdapter {
+    fn open_pane(&self, direction: Direction, size: i32) -> Result<String> {
+        info!("Open tmux pane: {}", direction.to_string());
+        let pane_size = format!("{}%", size);
+        let split = match direction {
+            Direction::Right => "-h",
+            Direction::Down => "-v",
+        };
+
+        let output = Command::new("tmux")
+            .args([
+                "split-window",
+                split,
+                "-l",
+                &pane_size,
+                "-P",
+                "-F",
+                "#{pane_id}",
+            ])
+            .output()
+            .with_context(|| TerminalError::OpenPane("Failed to run tmux command".to_string()))?;
+
+        let stdout = String::from_utf8(output.stdout)
+            .with_context(|| TerminalError::OpenPane("Failed to parse tmux output".to_string()))?;
+
+        let pane_id = stdout.trim();
+        if pane_id.is_empty() {
+            Err(TerminalError::OpenPane(format!("There is no pane {direction}")).into())
+        } else {
+            Ok(pane_id.to_string())
+        }
+    }
+
+    fn close_pane(&self, pane_id: &str) -> Result<()> {
+        Command::new("tmux")
+            .args(["kill-pane", "-t", pane_id])
+            .output()
+            .with_context(|| {
+                TerminalError::ClosePane(format!("Failed to close pane {}", pane_id))
+            })?;
+        Ok(())
+    }
+
+    fn pipe_text_to_pane(&self, mut invocation: Invocation, pane_id: &str) -> Result<ExitStatus> {
+        let job = PaneJob::new(pane_id);
+        job.prepare(&mut invocation)?;
+
+        let sent = Command::new("tmux")
+            .args([
+                "send-keys",
+                "-t",
+                pane_id,
+                &job.command_line(&invocation),
+                "Enter",
+            ])
+            .status()
+            .with_context(|| {
+                TerminalError::PipeText(format!("Failed to pipe text to pane {}", pane_id))
+            })?;
+        // The keys are lost when the pane is gone, nothing would report a status
+        if !sent.success() {
+            return Err(TerminalError::PipeText(format!(
+                "tmux could not send keys to pane {}",
+                pane_id
+            ))
+            .into());
+        }
+
+        job.wait(|| {
+            Command::new("tmux")
+                .args(["display-message", "-p", "-t", pane_id, "#{pane_id}"])
+                .output()
+                .map(|output| output.status.success())
+                .unwrap_or(false)
+        })
+    }
+
+    fn signal_task(&self, pane_id: &str, signal: Signal) -> Result<()> {
+        info!("Send SIG{} to the task in pane {}", signal.name(), pane_id);
+        PaneJob::new(pane_id).signal(signal)
+    }
+
+    fn set_pane_title(&self, pane_id: &str, title: &str) -> Result<()> {
+        info!("Set title of pane {}: {}", pane_id, title);
+        tmux(&["select-pane", "-t", pane_id, "-T", title])
+            .with_context(|| TerminalError::Title(format!("Failed to title pane {}", pane_id)))
+    }
+
+    fn set_tab_title(&self, pane_id: &str, title: &str) -> Result<()> {
+        info!("Set title of window holding pane {}: {}", pane_id, title);
+        let result = if title.is_empty() {
+            tmux(&["set-option", "-w", "-t", pane_id, "automatic-rename", "on"])
+        } else {
+            tmux(&["rename-window", "-t", pane_id, title])
+        };
+        result
+            .with_context(|| TerminalError::Title(format!("Failed to title window of {}", pane_id)))
+    }
+
+    fn notify(&self, pane_id: &str, _title: &str, body: &str) -> Result<()> {
+        info!("Notify from pane {}: {}", pane_id, body);
+        write_passthrough(pane_id, &osc9_notify(body))
+            .with_context(|| TerminalError::Notify(format!("Failed to notify from {}", pane_id)))
+    }
+
+    fn set_user_var(&self, pane_id: &str, name: &str, value: &str) -> Result<()> {
+        info!("Set user var on pane {}: {}={}", pane_id, name, value);
+        write_passthrough(pane_id, &osc_user_var(name, value)).with_context(|| {
+            TerminalError::UserVar(format!("Failed to set {} on {}", name, pane_id))
+        })
+    }
+}
+
+/// Forward an escape sequence to the outer terminal through the pane's tty.
+/// Needs `set -g allow-passthrough on` in tmux.
+fn write_passthrough(pane_id: &str, sequence: &str) -> Result<()> {
+    let output = Command::new("tmux")
+        .args(["display-message", "-p", "-t", pane_id, "#{pane_tty}"])
+        .output()?;
+    let tty = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    OpenOptions::new()
+        .write(true)
+        .open(&tty)
+        .and_then(|mut device| device.write_all(tmux_passthrough(sequence).as_bytes()))
+        .with_context(|| format!("Failed to write to {}", tty))
+}
+
+/// Run a tmux command, failing on a non-zero exit
+fn tmux(args: &[&str]) -> Result<()> {
+    let output = Command::new("tmux").args(args).output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!(String::from_utf8_lossy(&output.stderr)
+            .trim()
+            .to_string()))
+    }
+}
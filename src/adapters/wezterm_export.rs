@@ -0,0 +1,433 @@
+//! Generates `wezterm.lua` snippets from the project configuration
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::domain::models::{Command, ConfigError, ProjectConfig};
+
+/// Icon of palette entries for tasks without an `icon`
+const DEFAULT_ICON: &str = "md_play";
+
+/// Output formats of the exports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Lua,
+}
+
+/// How a generated key binding starts its task
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    /// `act.SpawnCommandInNewTab` running `wzb task-runner <task>`
+    Spawn,
+    /// `act.EmitEvent` handled by a `wezterm.on` callback in the module
+    Event,
+}
+
+/// A task bound to a key with optional modifiers
+struct Binding<'a> {
+    task: &'a str,
+    key: String,
+    mods: String,
+}
+
+/// Registers are single letter tasks, e.g. `q` or `Q`
+fn is_register(name: &str) -> bool {
+    name.len() == 1 && name.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+fn is_configured(command: &Command) -> bool {
+    command.is_runnable()
+}
+
+/// Lua module binding `LEADER + <register>` and task `key`s to their tasks
+pub fn keys_module(config: &ProjectConfig, action: KeyAction) -> Result<String> {
+    let mut names = config.tasks.keys().collect::<Vec<_>>();
+    names.sort();
+
+    let mut bindings = vec![];
+    for name in &names {
+        let command = &config.tasks[*name];
+        if is_register(name) && is_configured(command) {
+            let uppercase = name.chars().all(|c| c.is_ascii_uppercase());
+            bindings.push(Binding {
+                task: name,
+                key: name.to_string(),
+                mods: if uppercase { "LEADER|SHIFT" } else { "LEADER" }.to_string(),
+            });
+        }
+    }
+    for name in &names {
+        if let Some(key) = &config.tasks[*name].key {
+            let (key, mods) = parse_key(key)?;
+            bindings.push(Binding {
+                task: name,
+                key,
+                mods,
+            });
+        }
+    }
+
+    let regenerate = match action {
+        KeyAction::Spawn => "wzb wezterm keys",
+        KeyAction::Event => "wzb wezterm keys --action event",
+    };
+    let mut lua = vec![
+        "-- Generated by `wzb wezterm keys`, do not edit by hand.".to_string(),
+        format!("-- Regenerate with: {} > wzb_keys.lua", regenerate),
+        "local wezterm = require(\"wezterm\")".to_string(),
+        "local act = wezterm.action".to_string(),
+        String::new(),
+        "local M = {}".to_string(),
+        String::new(),
+    ];
+
+    if action == KeyAction::Event {
+        lua.extend([
+            "local function run_task(name)".to_string(),
+            "  return function(window, pane)".to_string(),
+            "    local cwd = pane:get_current_working_dir()".to_string(),
+            "    wezterm.background_child_process({".to_string(),
+            "      \"wzb\", \"--backend\", \"wezterm\",".to_string(),
+            "      \"-C\", cwd and cwd.file_path or \".\",".to_string(),
+            "      \"task-runner\", name,".to_string(),
+            "    })".to_string(),
+            "  end".to_string(),
+            "end".to_string(),
+            String::new(),
+        ]);
+        let mut tasks = bindings.iter().map(|b| b.task).collect::<Vec<_>>();
+        tasks.sort();
+        tasks.dedup();
+        lua.extend(tasks.iter().map(|task| {
+            format!(
+                "wezterm.on({}, run_task({}))",
+                lua_string(&event_name(task)),
+                lua_string(task)
+            )
+        }));
+        lua.push(String::new());
+    }
+
+    lua.push("M.keys = {".to_string());
+    lua.extend(bindings.iter().map(|binding| {
+        let action = match action {
+            KeyAction::Spawn => format!(
+                "act.SpawnCommandInNewTab({{ args = {{ \"wzb\", \"task-runner\", {} }} }})",
+                lua_string(binding.task)
+            ),
+            KeyAction::Event => format!("act.EmitEvent({})", lua_string(&event_name(binding.task))),
+        };
+        format!(
+            "  {{ key = {}, mods = {}, action = {} }},",
+            lua_string(&binding.key),
+            lua_string(&binding.mods),
+            action
+        )
+    }));
+    lua.extend([
+        "}".to_string(),
+        String::new(),
+        "function M.apply_to_config(config)".to_string(),
+        "  config.keys = config.keys or {}".to_string(),
+        "  for _, binding in ipairs(M.keys) do".to_string(),
+        "    table.insert(config.keys, binding)".to_string(),
+        "  end".to_string(),
+        "end".to_string(),
+        String::new(),
+        "return M".to_string(),
+    ]);
+
+    Ok(lua.join("\n"))
+}
+
+/// A command palette entry for `augment-command-palette`
+#[derive(Serialize)]
+struct PaletteEntry {
+    brief: String,
+    doc: String,
+    icon: String,
+    args: Vec<String>,
+    cwd: String,
+}
+
+/// A `launch_menu` entry
+#[derive(Serialize)]
+struct LaunchMenuEntry {
+    label: String,
+    args: Vec<String>,
+    cwd: String,
+}
+
+fn task_args(task: &str) -> Vec<String> {
+    vec![
+        "wzb".to_string(),
+        "task-runner".to_string(),
+        task.to_string(),
+    ]
+}
+
+fn describe(command: &Command) -> String {
+    command
+        .description
+        .clone()
+        .unwrap_or_else(|| command.command_line())
+}
+
+/// Configured, visible tasks sorted by name
+fn exported_tasks(config: &ProjectConfig) -> Vec<(&String, &Command)> {
+    let mut tasks = config
+        .tasks
+        .iter()
+        .filter(|(name, command)| is_configured(command) && !command.is_hidden(name))
+        .collect::<Vec<_>>();
+    tasks.sort_by_key(|(name, _)| *name);
+    tasks
+}
+
+/// Command palette entries running each task in `project_root`
+pub fn palette(config: &ProjectConfig, project_root: &str, format: ExportFormat) -> Result<String> {
+    let entries = exported_tasks(config)
+        .into_iter()
+        .map(|(name, command)| PaletteEntry {
+            brief: format!("wzb: {}", name),
+            doc: describe(command),
+            icon: command
+                .icon
+                .clone()
+                .unwrap_or_else(|| DEFAULT_ICON.to_string()),
+            args: task_args(name),
+            cwd: project_root.to_string(),
+        })
+        .collect::<Vec<_>>();
+
+    match format {
+        ExportFormat::Json => Ok(serde_json::to_string_pretty(&entries)?),
+        ExportFormat::Lua => Ok(lua_table(
+            "wzb wezterm palette --format lua",
+            entries.iter().map(|entry| {
+                format!(
+                    "  {{ brief = {}, doc = {}, icon = {}, action = act.SpawnCommandInNewTab({{ args = {}, cwd = {} }}) }},",
+                    lua_string(&entry.brief),
+                    lua_string(&entry.doc),
+                    lua_string(&entry.icon),
+                    lua_list(&entry.args),
+                    lua_string(&entry.cwd)
+                )
+            }),
+        )),
+    }
+}
+
+/// `launch_menu` entries running each task in `project_root`
+pub fn launch_menu(
+    config: &ProjectConfig,
+    project_root: &str,
+    format: ExportFormat,
+) -> Result<String> {
+    let entries = exported_tasks(config)
+        .into_iter()
+        .map(|(name, command)| LaunchMenuEntry {
+            label: match &command.description {
+                Some(description) => format!("{}: {}", name, description),
+                None => name.to_string(),
+            },
+            args: task_args(name),
+            cwd: project_root.to_string(),
+        })
+        .collect::<Vec<_>>();
+
+    match format {
+        ExportFormat::Json => Ok(serde_json::to_string_pretty(&entries)?),
+        ExportFormat::Lua => Ok(lua_table(
+            "wzb wezterm launch-menu --format lua",
+            entries.iter().map(|entry| {
+                format!(
+                    "  {{ label = {}, args = {}, cwd = {} }},",
+                    lua_string(&entry.label),
+                    lua_list(&entry.args),
+                    lua_string(&entry.cwd)
+                )
+            }),
+        )),
+    }
+}
+
+/// Lua chunk returning a list of entries
+fn lua_table(regenerate: &str, entries: impl Iterator<Item = String>) -> String {
+    let mut lua = vec![
+        format!("-- Generated by `{}`, do not edit by hand.", regenerate),
+        "local wezterm = require(\"wezterm\")".to_string(),
+        "local act = wezterm.action".to_string(),
+        String::new(),
+        "return {".to_string(),
+    ];
+    lua.extend(entries);
+    lua.push("}".to_string());
+    lua.join("\n")
+}
+
+fn lua_list(values: &[String]) -> String {
+    format!(
+        "{{ {} }}",
+        values
+            .iter()
+            .map(|value| lua_string(value))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+fn event_name(task: &str) -> String {
+    format!("wzb-task-{}", task)
+}
+
+/// Turn `ctrl-shift-b` into WezTerm's `("b", "CTRL|SHIFT")`
+fn parse_key(spec: &str) -> Result<(String, String)> {
+    let invalid = |reason: &str| ConfigError::Parse(format!("Invalid key '{}': {}", spec, reason));
+
+    let parts = spec.split('-').collect::<Vec<_>>();
+    let (key, modifiers) = parts
+        .split_last()
+        .filter(|(key, _)| !key.is_empty())
+        .ok_or_else(|| invalid("missing key"))?;
+
+    let mut mods = vec![];
+    for modifier in modifiers {
+        let wezterm_mod = match modifier.to_lowercase().as_str() {
+            "ctrl" | "control" => "CTRL",
+            "shift" => "SHIFT",
+            "alt" | "opt" | "option" | "meta" => "ALT",
+            "super" | "cmd" | "win" => "SUPER",
+            "leader" => "LEADER",
+            _ => return Err(invalid(&format!("unknown modifier '{}'", modifier)).into()),
+        };
+        if !mods.contains(&wezterm_mod) {
+            mods.push(wezterm_mod);
+        }
+    }
+
+    let mods = if mods.is_empty() {
+        "NONE".to_string()
+    } else {
+        mods.join("|")
+    };
+    Ok((key.to_string(), mods))
+}
+
+fn lua_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(program: &str, key: Option<&str>) -> Command {
+        Command {
+            program: program.to_string(),
+            key: key.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn config() -> ProjectConfig {
+        let mut config = ProjectConfig::default();
+        config.tasks.insert("q".to_string(), command("cargo", None));
+        config.tasks.insert("Q".to_string(), command("cargo", None));
+        config.tasks.insert("w".to_string(), command("", None));
+        config
+            .tasks
+            .insert("build".to_string(), command("cargo", Some("ctrl-b")));
+        config
+            .tasks
+            .insert("test".to_string(), command("cargo", None));
+        config
+    }
+
+    #[test]
+    fn test_keys_module_spawn() {
+        let lua = keys_module(&config(), KeyAction::Spawn).unwrap();
+
+        let bindings = lua
+            .lines()
+            .filter(|line| line.trim_start().starts_with("{ key"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bindings,
+            vec![
+                r#"  { key = "Q", mods = "LEADER|SHIFT", action = act.SpawnCommandInNewTab({ args = { "wzb", "task-runner", "Q" } }) },"#,
+                r#"  { key = "q", mods = "LEADER", action = act.SpawnCommandInNewTab({ args = { "wzb", "task-runner", "q" } }) },"#,
+                r#"  { key = "b", mods = "CTRL", action = act.SpawnCommandInNewTab({ args = { "wzb", "task-runner", "build" } }) },"#,
+            ]
+        );
+        assert!(lua.ends_with("return M"));
+    }
+
+    #[test]
+    fn test_keys_module_is_deterministic() {
+        let first = keys_module(&config(), KeyAction::Event).unwrap();
+        let second = keys_module(&config(), KeyAction::Event).unwrap();
+        assert_eq!(first, second);
+        assert!(first.contains(r#"wezterm.on("wzb-task-build", run_task("build"))"#));
+        assert!(first.contains(r#"action = act.EmitEvent("wzb-task-q")"#));
+    }
+
+    #[test]
+    fn test_palette_json() {
+        let mut config = config();
+        config.tasks.get_mut("build").unwrap().description = Some("Build it".to_string());
+        config.tasks.get_mut("build").unwrap().icon = Some("md_hammer".to_string());
+
+        let json = palette(&config, "/work/project", ExportFormat::Json).unwrap();
+        let entries: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+
+        // Sorted by name, unconfigured registers are skipped
+        let briefs = entries
+            .iter()
+            .map(|entry| entry["brief"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(briefs, vec!["wzb: Q", "wzb: build", "wzb: q", "wzb: test"]);
+        assert_eq!(entries[1]["doc"], "Build it");
+        assert_eq!(entries[1]["icon"], "md_hammer");
+        assert_eq!(entries[3]["doc"], "cargo");
+        assert_eq!(entries[3]["icon"], DEFAULT_ICON);
+        assert_eq!(entries[1]["cwd"], "/work/project");
+        assert_eq!(
+            entries[1]["args"],
+            serde_json::json!(["wzb", "task-runner", "build"])
+        );
+    }
+
+    #[test]
+    fn test_palette_and_launch_menu_lua() {
+        let lua = palette(&config(), "/work/project", ExportFormat::Lua).unwrap();
+        assert!(lua.contains(
+            r#"  { brief = "wzb: build", doc = "cargo", icon = "md_play", action = act.SpawnCommandInNewTab({ args = { "wzb", "task-runner", "build" }, cwd = "/work/project" }) },"#
+        ));
+
+        let lua = launch_menu(&config(), "/work/project", ExportFormat::Lua).unwrap();
+        assert!(lua.contains(
+            r#"  { label = "test", args = { "wzb", "task-runner", "test" }, cwd = "/work/project" },"#
+        ));
+        assert!(lua.ends_with("}"));
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(
+            parse_key("ctrl-b").unwrap(),
+            ("b".to_string(), "CTRL".to_string())
+        );
+        assert_eq!(
+            parse_key("cmd-shift-F5").unwrap(),
+            ("F5".to_string(), "SUPER|SHIFT".to_string())
+        );
+        assert_eq!(
+            parse_key("F6").unwrap(),
+            ("F6".to_string(), "NONE".to_string())
+        );
+        assert!(parse_key("hyper-b").is_err());
+        assert!(parse_key("ctrl-").is_err());
+    }
+}
@@ -1,32 +1,76 @@
 use anyhow::{Context, Result};
+use clap::Parser;
+use std::env;
 use std::process::ExitStatus;
 
-use crate::adapters::cli_adapter::CliAdapter;
+use crate::adapters::backend_registry::{
+    BackendOverrides, BackendRegistry, Environment, BACKEND_ENV,
+};
+use crate::adapters::cli_adapter::{Cli, CliAdapter};
 use crate::adapters::config_adapter::ConfigAdapter;
+use crate::adapters::diagnostics_adapter::DiagnosticsAdapter;
+use crate::adapters::environment_adapter::EnvironmentAdapter;
 use crate::adapters::file_adapter::FileAdapter;
-use crate::adapters::terminal_adapter::TerminalAdapter;
+use crate::adapters::paths::{find_project_root, state_dir};
+use crate::adapters::prompt_adapter::PromptAdapter;
+use crate::adapters::state_adapter::StateAdapter;
 use crate::constants::{CONFIG_FILE, DOTDIR};
-use crate::domain::behaviours::TaskExecutionService;
+use crate::domain::behaviours::{DiagnosticsService, TaskExecutionService};
+use crate::domain::variables::VariableService;
+use crate::ports::ConfigPort;
 
 pub struct Application;
 
 impl Application {
     pub fn run() -> Result<ExitStatus> {
+        let cli = Cli::parse();
+
+        // Use the nearest project with a config, or the current directory
+        let current_dir = match cli.directory() {
+            Some(directory) => directory.to_path_buf(),
+            None => env::current_dir().context("Failed to read current directory")?,
+        };
+        let project_root = find_project_root(&current_dir).unwrap_or(current_dir);
+
         // Create adapters
         let file_adapter = FileAdapter::new();
 
         let config_adapter = ConfigAdapter::new(
             file_adapter.clone(),
-            DOTDIR.to_string(),
+            project_root.join(DOTDIR).to_string_lossy().to_string(),
             CONFIG_FILE.to_string(),
         );
 
-        let terminal_adapter = TerminalAdapter::new();
+        let state_adapter = StateAdapter::new(state_dir(&project_root));
+
+        let diagnostics_adapter = DiagnosticsAdapter::new(file_adapter.clone(), project_root);
+
+        // Pick the terminal backend, the config file is optional at this point
+        let overrides = BackendOverrides {
+            flag: cli.backend(),
+            env: env::var(BACKEND_ENV).ok().filter(|value| !value.is_empty()),
+            config: config_adapter
+                .load_config()
+                .ok()
+                .and_then(|config| config.settings.backend),
+        };
+        let backend = BackendRegistry::select(overrides, Environment::detect(&file_adapter))?;
 
-        let task_execution_service = TaskExecutionService::new(terminal_adapter);
+        let terminal_adapter = BackendRegistry::create(backend.kind);
 
-        let cli_adapter = CliAdapter::new(config_adapter, task_execution_service);
+        let task_execution_service = TaskExecutionService::new(terminal_adapter, state_adapter);
+
+        let diagnostics_service = DiagnosticsService::new(diagnostics_adapter);
+
+        let cli_adapter = CliAdapter::new(
+            config_adapter,
+            task_execution_service,
+            diagnostics_service,
+            VariableService::new(EnvironmentAdapter::new()),
+            backend,
+            PromptAdapter::new(),
+        );
 
-        cli_adapter.run().context("Failed to run application")
+        cli_adapter.run(cli).context("Failed to run application")
     }
 }
@@ -1,5 +1,8 @@
+pub const BIN_NAME: &str = "wzb";
+pub const VERSION: &str = "0.8.0";
 pub const DOTDIR: &str = ".wez";
 pub const CONFIG_FILE: &str = "config.toml";
+pub const STATE_DIR: &str = "wez-bits";
 pub const BANNER: &str = r#"
                                          /$$       /$$   /$$             
                                         | $$      |__/  | $$             
@@ -1,61 +1,2127 @@
 use anyhow::{anyhow, Result};
-use std::{process::ExitStatus, slice};
+use log::{info, warn};
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::os::unix::process::ExitStatusExt;
+use std::path::Path;
+use std::process::{self, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use super::models::{Direction, DomainError, Task, TaskConfig, TaskSettings};
-use crate::{domain::models::TaskClose, ports::TerminalPort};
+use super::models::{
+    format_duration, BackendKind, Check, CheckStatus, Command, Direction, DomainError, FailureKind,
+    HistoryEntry, HookKind, Interval, Invocation, ProjectConfig, RetryPolicy, RunStatus, Signal,
+    Step, Task, TaskConfig, TaskOutcome, TaskRun, TaskSettings,
+};
+use crate::{
+    domain::{models::TaskClose, params::substitute},
+    ports::{DiagnosticsPort, StatePort, TerminalPort},
+};
+
+/// How long a timed out task gets to exit after each signal before the next one
+const TIMEOUT_GRACE: Duration = Duration::from_secs(10);
+
+/// How a task ended in its pane, after any retries
+struct Finished {
+    status: ExitStatus,
+    timed_out: bool,
+    attempts: u32,
+}
+
+impl Finished {
+    /// The task's status, or the timeout's own code so scripts can tell a
+    /// timeout from the task being interrupted
+    fn exit_status(&self) -> ExitStatus {
+        if self.timed_out {
+            ExitStatus::from_raw(i32::from(FailureKind::TimedOut.exit_code()) << 8)
+        } else {
+            self.status
+        }
+    }
+}
 
 /// The core application service for task execution
-pub struct TaskExecutionService<P: TerminalPort> {
+pub struct TaskExecutionService<P: TerminalPort, S: StatePort> {
     terminal_controller: P,
+    state: S,
 }
 
-impl<P: TerminalPort> TaskExecutionService<P> {
-    pub fn new(terminal_controller: P) -> Self {
+impl<P: TerminalPort, S: StatePort> TaskExecutionService<P, S> {
+    pub fn new(terminal_controller: P, state: S) -> Self {
         Self {
             terminal_controller,
+            state,
         }
     }
 
     pub fn execute_task(&self, task: Task) -> Result<ExitStatus> {
+        self.run_task(task).map(|finished| finished.exit_status())
+    }
+
+    /// Run the task in a new pane between its hooks, and again in the same pane
+    /// while it fails with retries left. Every attempt is recorded to the history.
+    fn run_task(&self, task: Task) -> Result<Finished> {
         let pane_id = self
             .terminal_controller
             .open_pane(task.settings.direction, 30)?;
-        let result = self.execute_interactive_task(&pane_id, &task);
 
-        match task.settings.close {
-            TaskClose::Always | TaskClose::OnSuccess if result.is_ok() => {
-                self.terminal_controller.close_pane(&pane_id)?
+        let mut run = TaskRun {
+            name: task.name.clone(),
+            status: RunStatus::Running,
+            pane_id: pane_id.clone(),
+            pid: process::id(),
+            started_at: unix_now(),
+            duration_ms: None,
+            exit_code: None,
+        };
+        self.publish(&run);
+        self.set_titles(&pane_id, &format!("▶ {}", task.name));
+
+        // A failing `before` hook stands in for the task, which doesn't run
+        let mut hook_failure = self.run_before_hooks(&pane_id, &task);
+        let hook_failed = hook_failure.is_some();
+
+        let retry = &task.settings.retry;
+        let started = Instant::now();
+        let mut banner = None;
+        let mut attempt = 1;
+        let (result, timed_out, attempt_elapsed) = loop {
+            let attempt_started_at = unix_now();
+            let attempt_started = Instant::now();
+            let (result, timed_out) = match hook_failure.take() {
+                Some(failure) => (failure, false),
+                None => self.execute_interactive_task(&pane_id, &task, banner.take()),
+            };
+            let elapsed = attempt_started.elapsed();
+
+            let finished = TaskRun {
+                started_at: attempt_started_at,
+                duration_ms: Some(elapsed.as_millis() as u64),
+                exit_code: result.as_ref().ok().and_then(ExitStatus::code),
+                status: run_status(&result, timed_out),
+                ..run.clone()
+            };
+            self.record_history(&task, &finished, (retry.retries > 0).then_some(attempt));
+
+            let failure = match &result {
+                Ok(status) if timed_out || !status.success() => Some(*status),
+                _ => None,
+            };
+            let Some(status) = failure
+                .filter(|status| !hook_failed && retry.should_retry(attempt, status, timed_out))
+            else {
+                break (result, timed_out, elapsed);
+            };
+
+            let reason = failure_reason(&status, timed_out);
+            attempt += 1;
+            info!(
+                "Task {} failed with {}, retrying in {}",
+                task.name,
+                reason,
+                format_duration(retry.delay)
+            );
+            self.set_titles(
+                &pane_id,
+                &format!("↻ {} (attempt {}/{})", task.name, attempt, retry.attempts()),
+            );
+            banner = Some(format!(
+                "↻ attempt {}/{} after {}",
+                attempt,
+                retry.attempts(),
+                reason
+            ));
+            thread::sleep(retry.delay);
+        };
+        let elapsed = started.elapsed();
+
+        run.duration_ms = Some(elapsed.as_millis() as u64);
+        run.exit_code = result.as_ref().ok().and_then(ExitStatus::code);
+        run.status = run_status(&result, timed_out);
+        self.publish(&run);
+        if attempt > 1 {
+            info!("Task {} ran {} times", task.name, attempt);
+        }
+
+        if let Ok(status) = &result {
+            let title = if timed_out {
+                let detail = format!("timed out after {}", format_duration(attempt_elapsed));
+                format!("⏱ {} ({})", task.name, with_attempts(detail, attempt))
+            } else {
+                finished_title(&task.name, status, elapsed, attempt)
+            };
+            self.set_titles(&pane_id, &title);
+            if task.settings.notify.should_notify(elapsed) {
+                self.notify(&pane_id, &task.name, status, elapsed, timed_out, attempt);
             }
-            _ => (),
+        }
+        // Even when the task couldn't run, e.g. to tear down what `before` set up
+        self.run_after_hooks(&pane_id, &task, &run);
+
+        // A timed out task failed, whatever its exit status
+        let close = match (&task.settings.close, &result) {
+            (TaskClose::Always, Ok(_)) => true,
+            (TaskClose::OnSuccess, Ok(status)) => status.success() && !timed_out,
+            _ => false,
         };
+        if close {
+            // The tab outlives the task pane, give it back its default title
+            self.set_tab_title(&pane_id, "");
+            self.terminal_controller.close_pane(&pane_id)?
+        }
 
-        result
+        result.map(|status| Finished {
+            status,
+            timed_out,
+            attempts: attempt,
+        })
+    }
+
+    /// Run `tasks` in order, up to `parallel` at once. Once one fails no
+    /// further task starts unless `keep_going`, those are left unstarted.
+    pub fn execute_tasks(
+        &self,
+        tasks: Vec<Task>,
+        keep_going: bool,
+        parallel: NonZeroUsize,
+    ) -> Vec<TaskOutcome> {
+        let outcomes = Mutex::new(
+            tasks
+                .iter()
+                .map(|task| TaskOutcome {
+                    name: task.name.clone(),
+                    result: None,
+                    elapsed: Duration::ZERO,
+                    timed_out: false,
+                    attempts: 0,
+                })
+                .collect::<Vec<_>>(),
+        );
+        let workers = parallel.get().min(tasks.len());
+        let queue = Mutex::new(tasks.into_iter().enumerate());
+        let failed = AtomicBool::new(false);
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    if failed.load(Ordering::SeqCst) && !keep_going {
+                        break;
+                    }
+                    let Some((index, task)) = queue.lock().unwrap().next() else {
+                        break;
+                    };
+                    let started = Instant::now();
+                    let result = self.run_task(task);
+                    let outcome = &mut outcomes.lock().unwrap()[index];
+                    outcome.timed_out = matches!(
+                        result,
+                        Ok(Finished {
+                            timed_out: true,
+                            ..
+                        })
+                    );
+                    outcome.attempts = result.as_ref().map_or(1, |finished| finished.attempts);
+                    outcome.result = Some(result.map(|finished| finished.exit_status()));
+                    outcome.elapsed = started.elapsed();
+                    if !outcome.succeeded() {
+                        failed.store(true, Ordering::SeqCst);
+                    }
+                });
+            }
+        });
+        outcomes.into_inner().unwrap()
+    }
+
+    /// Latest state of every task of the project
+    pub fn task_runs(&self) -> Result<Vec<TaskRun>> {
+        self.state.load_runs()
+    }
+
+    /// Every finished run of the project, oldest first
+    pub fn history(&self) -> Result<Vec<HistoryEntry>> {
+        self.state.load_history()
+    }
+
+    /// Like publishing, failing to record history never fails the task
+    fn record_history(&self, task: &Task, run: &TaskRun, attempt: Option<u32>) {
+        let entry = HistoryEntry {
+            name: task.name.clone(),
+            command: task.command.command_line(),
+            cwd: task.command.cwd.clone(),
+            started_at: run.started_at,
+            duration_ms: run.duration_ms.unwrap_or_default(),
+            exit_code: run.exit_code,
+            timed_out: run.status == RunStatus::TimedOut,
+            attempt,
+            timeout: task.settings.timeout.map(Interval),
+            pane_id: run.pane_id.clone(),
+            close: task.settings.close,
+            direction: task.settings.direction,
+            extra_args: task.extra_args.clone(),
+            params: task.params.clone(),
+        };
+        if let Err(err) = self.state.append_history(&entry) {
+            warn!("{:#}", err);
+        }
+    }
+
+    /// Persist the run and mirror it into pane user vars for `wezterm.lua`.
+    /// Like titles, failing to publish never fails the task.
+    fn publish(&self, run: &TaskRun) {
+        if let Err(err) = self.state.record_run(run) {
+            warn!("{:#}", err);
+        }
+
+        let mut vars = vec![
+            ("wzb_task", run.name.clone()),
+            ("wzb_status", run.status.to_string()),
+            ("wzb_started_at", run.started_at.to_string()),
+        ];
+        vars.extend(
+            run.exit_code
+                .map(|code| ("wzb_exit_code", code.to_string())),
+        );
+        vars.extend(
+            run.duration_ms
+                .map(|ms| ("wzb_duration_ms", ms.to_string())),
+        );
+
+        for (name, value) in vars {
+            if let Err(err) = self
+                .terminal_controller
+                .set_user_var(&run.pane_id, name, &value)
+            {
+                warn!("{:#}", err);
+            }
+        }
+    }
+
+    /// Titles are cosmetic, failing to set them never fails the task
+    fn set_titles(&self, pane_id: &str, title: &str) {
+        if let Err(err) = self.terminal_controller.set_pane_title(pane_id, title) {
+            warn!("{:#}", err);
+        }
+        self.set_tab_title(pane_id, title);
+    }
+
+    fn notify(
+        &self,
+        pane_id: &str,
+        name: &str,
+        status: &ExitStatus,
+        elapsed: Duration,
+        timed_out: bool,
+        attempts: u32,
+    ) {
+        let body = match status.code() {
+            _ if timed_out => format!("⏱ timed out after {}", format_duration(elapsed)),
+            Some(0) => format!("✔ finished in {}", format_duration(elapsed)),
+            Some(code) => format!(
+                "✘ failed with exit {} after {}",
+                code,
+                format_duration(elapsed)
+            ),
+            None => format!("✘ killed after {}", format_duration(elapsed)),
+        };
+        let body = with_attempts(body, attempts);
+        let title = format!("wzb: {}", name);
+        if let Err(err) = self.terminal_controller.notify(pane_id, &title, &body) {
+            warn!("{:#}", err);
+        }
+    }
+
+    fn set_tab_title(&self, pane_id: &str, title: &str) {
+        if let Err(err) = self.terminal_controller.set_tab_title(pane_id, title) {
+            warn!("{:#}", err);
+        }
+    }
+
+    /// Run the `before` hooks, global ones first, up to the first that fails.
+    /// Returns that failure.
+    fn run_before_hooks(&self, pane_id: &str, task: &Task) -> Option<Result<ExitStatus>> {
+        for hooks in &task.settings.hooks {
+            let Some(hook) = hooks.get(HookKind::Before) else {
+                continue;
+            };
+            let result = self.run_hook(pane_id, task, HookKind::Before, hook, None);
+            match &result {
+                Ok(status) if status.success() => {}
+                Ok(status) => {
+                    warn!(
+                        "Before hook of {} failed with {}, skipping the task",
+                        task.name,
+                        failure_reason(status, false)
+                    );
+                    return Some(result);
+                }
+                Err(_) => return Some(result),
+            }
+        }
+        None
+    }
+
+    /// Run the `on_success` or `on_failure` hooks and then the `after` hooks
+    /// of a finished run, the task's own first. Like titles, a failing hook
+    /// never fails the task.
+    fn run_after_hooks(&self, pane_id: &str, task: &Task, run: &TaskRun) {
+        let outcome = match run.status {
+            RunStatus::Success => HookKind::OnSuccess,
+            _ => HookKind::OnFailure,
+        };
+        for kind in [outcome, HookKind::After] {
+            for hooks in task.settings.hooks.iter().rev() {
+                let Some(hook) = hooks.get(kind) else {
+                    continue;
+                };
+                match self.run_hook(pane_id, task, kind, hook, Some(run)) {
+                    Ok(status) if !status.success() => warn!(
+                        "{} hook of {} failed with {}",
+                        kind,
+                        task.name,
+                        failure_reason(&status, false)
+                    ),
+                    Ok(_) => {}
+                    Err(err) => warn!("{:#}", err),
+                }
+            }
+        }
+    }
+
+    /// Run a hook like a script task in the task pane, under the task's shell,
+    /// directory and environment, describing the task and a finished `run` in
+    /// `WZB_*` variables
+    fn run_hook(
+        &self,
+        pane_id: &str,
+        task: &Task,
+        kind: HookKind,
+        hook: &str,
+        run: Option<&TaskRun>,
+    ) -> Result<ExitStatus> {
+        let command = Command {
+            script: Some(hook.to_string()),
+            shell: task.command.shell.clone(),
+            ..Default::default()
+        };
+        let mut env = task.command.env.clone();
+        env.insert("WZB_TASK".to_string(), task.name.clone());
+        env.insert("WZB_HOOK".to_string(), kind.to_string());
+        if let Some(run) = run {
+            env.insert("WZB_STATUS".to_string(), run.status.to_string());
+            if let Some(code) = run.exit_code {
+                env.insert("WZB_EXIT_CODE".to_string(), code.to_string());
+            }
+            if let Some(ms) = run.duration_ms {
+                env.insert("WZB_DURATION_MS".to_string(), ms.to_string());
+            }
+        }
+        let first_line = hook.lines().next().unwrap_or_default().trim();
+        let invocation = Invocation {
+            steps: command.to_steps(),
+            cwd: task.command.cwd.clone(),
+            env,
+            timeout: None,
+            banner: Some(format!("▶ {}: {}", kind, first_line)),
+        };
+        info!("Run {} hook of {}", kind, task.name);
+        self.terminal_controller
+            .pipe_text_to_pane(invocation, pane_id)
     }
 
-    fn execute_interactive_task(&self, pane_id: &str, task: &Task) -> Result<ExitStatus> {
-        let args = [
-            slice::from_ref(&task.command.program),
-            task.command.args.as_slice(),
-        ]
-        .concat();
-        self.terminal_controller.pipe_text_to_pane(args, pane_id)
+    /// Wait for the task, interrupting it once its timeout elapses and escalating
+    /// to SIGTERM and then SIGKILL while it outlives each grace period
+    fn execute_interactive_task(
+        &self,
+        pane_id: &str,
+        task: &Task,
+        banner: Option<String>,
+    ) -> (Result<ExitStatus>, bool) {
+        let invocation = Invocation {
+            steps: task.command.to_steps(),
+            cwd: task.command.cwd.clone(),
+            env: task.command.env.clone(),
+            timeout: task.settings.timeout,
+            banner,
+        };
+        let Some(timeout) = task.settings.timeout else {
+            let result = self
+                .terminal_controller
+                .pipe_text_to_pane(invocation, pane_id);
+            return (result, false);
+        };
+
+        thread::scope(|scope| {
+            let (sender, receiver) = mpsc::channel();
+            scope.spawn(move || {
+                let result = self
+                    .terminal_controller
+                    .pipe_text_to_pane(invocation, pane_id);
+                let _ = sender.send(result);
+            });
+            if let Ok(result) = receiver.recv_timeout(timeout) {
+                return (result, false);
+            }
+
+            for signal in [Signal::Interrupt, Signal::Terminate, Signal::Kill] {
+                info!("Task {} timed out, sending SIG{}", task.name, signal.name());
+                if let Err(err) = self.terminal_controller.signal_task(pane_id, signal) {
+                    warn!("{:#}", err);
+                }
+                if let Ok(result) = receiver.recv_timeout(TIMEOUT_GRACE) {
+                    return (result, true);
+                }
+            }
+            let result = receiver
+                .recv()
+                .unwrap_or_else(|_| Err(anyhow!("Lost track of task {}", task.name)));
+            (result, true)
+        })
     }
 
+    /// Resolve a configured task, passing `extra_args` given after `--` to it
     pub fn find_task(
         &self,
         task_name: &str,
-        config: &TaskConfig,
+        config: &ProjectConfig,
         close: TaskClose,
         direction: Direction,
+        extra_args: &[String],
+        params: &BTreeMap<String, String>,
     ) -> Result<Task> {
-        match config.get(task_name) {
-            Some(command) => Ok(Task::new(
-                command.to_owned(),
-                TaskSettings { close, direction },
-            )),
-            None => Err(anyhow!(DomainError::FeatureNotConfigured(
-                task_name.to_string()
-            ))),
+        let task_name = resolve_task_name(config, task_name)?;
+        let task_name = task_name.as_str();
+        let command = &config.tasks[task_name];
+        let runs = [
+            !command.program.is_empty(),
+            !command.steps.is_empty(),
+            command.script.is_some(),
+        ];
+        if runs.iter().filter(|set| **set).count() > 1 {
+            return Err(DomainError::Configuration(format!(
+                "Task '{}' sets more than one of program, steps and script, keep one of them",
+                task_name
+            ))
+            .into());
+        }
+
+        Ok(Task {
+            extra_args: extra_args.to_vec(),
+            params: params.clone(),
+            ..Task::new(
+                task_name,
+                Command {
+                    args: expand_args(task_name, &command.args, extra_args, params)?,
+                    steps: expand_step_args(task_name, &command.steps, extra_args, params)?,
+                    env: command
+                        .env
+                        .iter()
+                        .map(|(name, value)| (name.clone(), substitute(value, params)))
+                        .collect(),
+                    shell: command.shell.clone().or(config.settings.shell.clone()),
+                    cwd: command.cwd.as_ref().map(|cwd| {
+                        Path::new(&config.root)
+                            .join(cwd)
+                            .to_string_lossy()
+                            .to_string()
+                    }),
+                    ..command.to_owned()
+                },
+                TaskSettings {
+                    close,
+                    direction,
+                    notify: command.notify.unwrap_or(config.settings.notify),
+                    timeout: command.timeout.map(|timeout| timeout.0),
+                    retry: RetryPolicy {
+                        retries: command.retries.unwrap_or_default(),
+                        delay: command.retry_delay.map(|delay| delay.0).unwrap_or_default(),
+                        on: command.retry_on.clone(),
+                    },
+                    hooks: vec![config.settings.hooks.clone(), command.hooks.clone()],
+                },
+            )
+        })
+    }
+}
+
+/// The configured task `name` refers to: the task itself, the task listing it
+/// in `aliases`, or the one visible task it is a prefix of. Names are case
+/// sensitive. A miss suggests the closest task names.
+pub fn resolve_task_name(config: &ProjectConfig, name: &str) -> Result<String, DomainError> {
+    if config.tasks.contains_key(name) {
+        return Ok(name.to_string());
+    }
+    let aliased = config
+        .tasks
+        .iter()
+        .filter(|(_, command)| command.aliases.iter().any(|alias| alias == name))
+        .map(|(task, _)| task.clone())
+        .collect::<Vec<_>>();
+    match aliased.as_slice() {
+        [task] => return Ok(task.clone()),
+        [] => {}
+        _ => {
+            return Err(DomainError::Configuration(format!(
+                "Alias '{}' is given by more than one task: {}",
+                name,
+                aliased.join(", ")
+            )))
+        }
+    }
+
+    let visible = config
+        .tasks
+        .iter()
+        .filter(|(task, command)| !command.is_hidden(task));
+    let mut prefixed = visible
+        .clone()
+        .filter(|(task, command)| {
+            task.starts_with(name) || command.aliases.iter().any(|alias| alias.starts_with(name))
+        })
+        .map(|(task, _)| task.clone())
+        .collect::<Vec<_>>();
+    match prefixed.len() {
+        1 => Ok(prefixed.remove(0)),
+        0 => {
+            let mut close = visible
+                .map(|(task, _)| (edit_distance(name, task), task.clone()))
+                .filter(|(distance, task)| *distance <= (task.chars().count() / 3).max(2))
+                .collect::<Vec<_>>();
+            close.sort();
+            Err(DomainError::UnknownTask(
+                name.to_string(),
+                close.into_iter().take(3).map(|(_, task)| task).collect(),
+            ))
+        }
+        _ => Err(DomainError::AmbiguousTask(name.to_string(), prefixed)),
+    }
+}
+
+/// Levenshtein distance between `a` and `b`, counted in chars
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// Place `extra` into `args`: `{args}` stands for all of them and `{1}`, `{2}`...
+/// for one each. Without placeholders the extra arguments are appended. `{name}`
+/// stands for the value of param `name`; values are placed in the same pass, so
+/// braces in them are kept as they are.
+pub fn expand_args(
+    task_name: &str,
+    args: &[String],
+    extra: &[String],
+    params: &BTreeMap<String, String>,
+) -> Result<Vec<String>, DomainError> {
+    if !args.iter().any(|arg| has_placeholder(arg)) {
+        let args = args.iter().map(|arg| substitute(arg, params));
+        return Ok(args.chain(extra.iter().cloned()).collect());
+    }
+
+    let mut expanded = vec![];
+    for arg in args {
+        if arg == "{args}" {
+            expanded.extend_from_slice(extra);
+            continue;
+        }
+
+        let mut rest = arg.as_str();
+        let mut value = String::new();
+        while let Some((before, name, after)) = next_placeholder(rest, params) {
+            value.push_str(before);
+            match name {
+                "args" => value.push_str(&extra.join(" ")),
+                name if params.contains_key(name) => value.push_str(&params[name]),
+                index => {
+                    let index = index.parse::<usize>().unwrap_or_default();
+                    let argument = index
+                        .checked_sub(1)
+                        .and_then(|index| extra.get(index))
+                        .ok_or_else(|| {
+                            DomainError::MissingArgument(task_name.to_string(), index)
+                        })?;
+                    value.push_str(argument);
+                }
+            }
+            rest = after;
+        }
+        value.push_str(rest);
+        expanded.push(value);
+    }
+    Ok(expanded)
+}
+
+/// Place `extra` into the steps with placeholders, or append it to the last step
+fn expand_step_args(
+    task_name: &str,
+    steps: &[Step],
+    extra: &[String],
+    params: &BTreeMap<String, String>,
+) -> Result<Vec<Step>, DomainError> {
+    let placed = steps
+        .iter()
+        .any(|step| step.args.iter().any(|arg| has_placeholder(arg)));
+    let last = steps.len().saturating_sub(1);
+
+    let mut expanded = steps.to_vec();
+    for (index, step) in expanded.iter_mut().enumerate() {
+        let has_placeholders = step.args.iter().any(|arg| has_placeholder(arg));
+        let extra = if has_placeholders || (!placed && index == last) {
+            extra
+        } else {
+            &[]
+        };
+        step.args = expand_args(task_name, &step.args, extra, params)?;
+    }
+    Ok(expanded)
+}
+
+/// Whether `arg` has a placeholder for extra arguments
+fn has_placeholder(arg: &str) -> bool {
+    next_placeholder(arg, &BTreeMap::new()).is_some()
+}
+
+/// Split `text` around its first `{args}`, `{<number>}` or `{<param>}`
+fn next_placeholder<'a>(
+    text: &'a str,
+    params: &BTreeMap<String, String>,
+) -> Option<(&'a str, &'a str, &'a str)> {
+    let mut offset = 0;
+    while let Some(start) = text[offset..].find('{').map(|start| start + offset) {
+        let end = start + text[start..].find('}')?;
+        let name = &text[start + 1..end];
+        if name == "args"
+            || (!name.is_empty() && name.chars().all(|c| c.is_ascii_digit()))
+            || params.contains_key(name)
+        {
+            return Some((&text[..start], name, &text[end + 1..]));
+        }
+        offset = start + 1;
+    }
+    None
+}
+
+/// Seconds since the Unix epoch
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default()
+}
+
+/// Compact one line summary of running and failed tasks, e.g. `▶ build ✘ lint`
+pub fn statusline(runs: &[TaskRun]) -> String {
+    let names = |statuses: &'static [RunStatus]| {
+        runs.iter()
+            .filter(move |run| statuses.contains(&run.status))
+            .map(|run| run.name.as_str())
+    };
+
+    [
+        ("▶", names(&[RunStatus::Running]).collect::<Vec<_>>()),
+        (
+            "✘",
+            names(&[RunStatus::Failed, RunStatus::TimedOut]).collect::<Vec<_>>(),
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, names)| !names.is_empty())
+    .map(|(marker, names)| format!("{} {}", marker, names.join(" ")))
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+/// The config with only the tasks in `namespace`, given with its trailing `:`
+pub fn in_namespace(config: &ProjectConfig, namespace: &str) -> Result<ProjectConfig, DomainError> {
+    let tasks = config
+        .tasks
+        .iter()
+        .filter(|(name, _)| name.starts_with(namespace))
+        .map(|(name, command)| (name.clone(), command.clone()))
+        .collect::<TaskConfig>();
+    if tasks.is_empty() {
+        return Err(DomainError::FeatureNotConfigured(namespace.to_string()));
+    }
+    Ok(ProjectConfig {
+        tasks,
+        ..config.clone()
+    })
+}
+
+/// Tasks grouped under their `group` heading with aligned descriptions.
+/// Hidden tasks are only listed, and marked, with `all`.
+pub fn task_list(config: &ProjectConfig, all: bool) -> String {
+    let tasks = config
+        .tasks
+        .iter()
+        .filter(|(name, command)| all || !command.is_hidden(name))
+        .collect::<Vec<_>>();
+    let grouped = tasks.iter().any(|(_, command)| command.group.is_some());
+    let width = tasks
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or_default();
+
+    // Named groups in order, ungrouped tasks last
+    let mut groups = tasks
+        .iter()
+        .map(|(_, command)| command.group.as_deref())
+        .collect::<Vec<_>>();
+    groups.sort_by_key(|group| (group.is_none(), *group));
+    groups.dedup();
+
+    let mut lines = vec![];
+    for group in groups {
+        if grouped {
+            lines.push(format!("{}:", group.unwrap_or("other")));
+        }
+        for (name, command) in tasks
+            .iter()
+            .filter(|(_, command)| command.group.as_deref() == group)
+        {
+            let summary = match &command.description {
+                Some(description) => description.clone(),
+                None if !command.is_runnable() => "(no program)".to_string(),
+                None => command.command_line(),
+            };
+            let hidden = if command.is_hidden(name) {
+                " (hidden)"
+            } else {
+                ""
+            };
+            let line = format!(
+                "{}{:<width$}  {}{}",
+                if grouped { "  " } else { "" },
+                name,
+                summary,
+                hidden,
+                width = width
+            );
+            lines.push(line.trim_end().to_string());
+        }
+    }
+    lines.join("\n")
+}
+
+/// One line per run: how long ago it started, its status, duration and command
+pub fn history_list(entries: &[HistoryEntry], now: u64) -> String {
+    let width = entries
+        .iter()
+        .map(|entry| entry.name.chars().count())
+        .max()
+        .unwrap_or_default();
+    entries
+        .iter()
+        .map(|entry| {
+            let ago = match now.saturating_sub(entry.started_at) {
+                0 => "now".to_string(),
+                seconds => format!("{} ago", format_duration(Duration::from_secs(seconds))),
+            };
+            let status = match entry.exit_code {
+                Some(0) => "✔".to_string(),
+                Some(code) => format!("✘ {}", code),
+                None => "✘".to_string(),
+            };
+            let line = format!(
+                "{:>11}  {:<width$}  {:<5} {:>7}  {}",
+                ago,
+                entry.name,
+                status,
+                format_duration(Duration::from_millis(entry.duration_ms)),
+                entry.command,
+                width = width
+            );
+            match entry.attempt {
+                Some(attempt) if attempt > 1 => format!("{}  (attempt {})", line, attempt),
+                _ => line,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// End of run table of each task's status and duration
+pub fn run_summary(outcomes: &[TaskOutcome]) -> String {
+    let width = outcomes
+        .iter()
+        .map(|outcome| outcome.name.chars().count())
+        .max()
+        .unwrap_or_default();
+    outcomes
+        .iter()
+        .map(|outcome| {
+            let (status, detail) = match &outcome.result {
+                None => ("- not run".to_string(), String::new()),
+                Some(Ok(_)) if outcome.timed_out => (
+                    "⏱ timed out".to_string(),
+                    with_attempts(format_duration(outcome.elapsed), outcome.attempts),
+                ),
+                Some(Ok(status)) => {
+                    let status = match (status.success(), status.code()) {
+                        (true, _) => "✔ success".to_string(),
+                        (false, Some(code)) => format!("✘ exit {}", code),
+                        (false, None) => {
+                            format!("✘ signal {}", status.signal().unwrap_or_default())
+                        }
+                    };
+                    let detail = format_duration(outcome.elapsed);
+                    (status, with_attempts(detail, outcome.attempts))
+                }
+                Some(Err(err)) => ("✘ error".to_string(), format!("{:#}", err)),
+            };
+            let line = format!(
+                "{:<width$}  {:<10} {}",
+                outcome.name,
+                status,
+                detail,
+                width = width
+            );
+            line.trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Title of a finished task pane, e.g. `✔ test (12s)`, `✘ test (exit 101)`
+/// or `✔ test (40s, 3 attempts)` once retried
+fn finished_title(name: &str, status: &ExitStatus, elapsed: Duration, attempts: u32) -> String {
+    let (mark, detail) = match (status.success(), status.code()) {
+        (true, _) => ("✔", format_duration(elapsed)),
+        (false, Some(code)) => ("✘", format!("exit {}", code)),
+        (false, None) => (
+            "✘",
+            format!("signal {}", status.signal().unwrap_or_default()),
+        ),
+    };
+    format!("{} {} ({})", mark, name, with_attempts(detail, attempts))
+}
+
+/// `detail` followed by the number of attempts, when there were several
+fn with_attempts(detail: String, attempts: u32) -> String {
+    match attempts {
+        0 | 1 => detail,
+        attempts => format!("{}, {} attempts", detail, attempts),
+    }
+}
+
+/// What a failed attempt ended with, e.g. `exit 1` or `timeout`
+fn failure_reason(status: &ExitStatus, timed_out: bool) -> String {
+    match status.code() {
+        _ if timed_out => "timeout".to_string(),
+        Some(code) => format!("exit {}", code),
+        None => format!("signal {}", status.signal().unwrap_or_default()),
+    }
+}
+
+fn run_status(result: &Result<ExitStatus>, timed_out: bool) -> RunStatus {
+    match result {
+        _ if timed_out => RunStatus::TimedOut,
+        Ok(status) if status.success() => RunStatus::Success,
+        _ => RunStatus::Failed,
+    }
+}
+
+/// Runs the `wzb doctor` checks against the environment
+pub struct DiagnosticsService<D: DiagnosticsPort> {
+    diagnostics: D,
+}
+
+impl<D: DiagnosticsPort> DiagnosticsService<D> {
+    pub fn new(diagnostics: D) -> Self {
+        Self { diagnostics }
+    }
+
+    /// Checks of the `backend` tasks run in, the config and the state directory
+    pub fn run_checks(&self, backend: BackendKind, config: Result<ProjectConfig>) -> Vec<Check> {
+        let mut checks = match backend {
+            BackendKind::WezTerm => self.check_wezterm(),
+            BackendKind::Tmux => vec![self.check_tmux()],
+            BackendKind::Local => vec![],
+        };
+        checks.extend(self.check_config(config));
+        checks.push(match self.diagnostics.check_state_dir() {
+            Ok(path) => Check::new("state directory", CheckStatus::Ok, path),
+            Err(err) => Check::new("state directory", CheckStatus::Error, format!("{:#}", err)),
+        });
+        checks
+    }
+
+    fn check_wezterm(&self) -> Vec<Check> {
+        let Some(path) = self.diagnostics.find_executable("wezterm") else {
+            return vec![Check::new(
+                "wezterm",
+                CheckStatus::Error,
+                "wezterm was not found on PATH",
+            )];
+        };
+
+        let version = match self.diagnostics.wezterm_version() {
+            Ok(version) => Check::new(
+                "wezterm",
+                CheckStatus::Ok,
+                format!("{} ({})", version, path),
+            ),
+            Err(err) => Check::new(
+                "wezterm",
+                CheckStatus::Error,
+                format!("{} could not report its version: {:#}", path, err),
+            ),
+        };
+
+        let mux = match self.diagnostics.list_panes() {
+            Ok(count) => Check::new(
+                "mux",
+                CheckStatus::Ok,
+                format!("reachable, {} panes", count),
+            ),
+            Err(err) => Check::new(
+                "mux",
+                CheckStatus::Error,
+                format!("wezterm cli list failed: {:#}", err),
+            ),
+        };
+
+        let pane = match self.diagnostics.current_pane() {
+            Some(pane) => Check::new(
+                "pane",
+                CheckStatus::Ok,
+                format!("inside WezTerm pane {}", pane),
+            ),
+            None => Check::new(
+                "pane",
+                CheckStatus::Warning,
+                "not inside a WezTerm pane (WEZTERM_PANE is not set)",
+            ),
+        };
+
+        vec![version, mux, pane]
+    }
+
+    fn check_tmux(&self) -> Check {
+        match self.diagnostics.find_executable("tmux") {
+            Some(path) => Check::new("tmux", CheckStatus::Ok, path),
+            None => Check::new("tmux", CheckStatus::Error, "tmux was not found on PATH"),
+        }
+    }
+
+    fn check_config(&self, config: Result<ProjectConfig>) -> Vec<Check> {
+        let Some(path) = self.diagnostics.config_path() else {
+            return vec![Check::new(
+                "config",
+                CheckStatus::Error,
+                "no config file found, run `wzb config create`",
+            )];
+        };
+
+        let config = match config {
+            Ok(config) => config,
+            Err(err) => {
+                return vec![Check::new(
+                    "config",
+                    CheckStatus::Error,
+                    format!("{} is invalid: {:#}", path, err),
+                )]
+            }
+        };
+
+        let mut names = config.tasks.keys().collect::<Vec<_>>();
+        names.sort();
+
+        let mut checks = vec![Check::new(
+            "config",
+            CheckStatus::Ok,
+            format!("{} ({} tasks)", path, names.len()),
+        )];
+        checks.extend(names.into_iter().filter_map(|name| {
+            let command = &config.tasks[name];
+            let label = format!("task {}", name);
+            if !command.is_runnable() {
+                return Some(Check::new(
+                    &label,
+                    CheckStatus::Warning,
+                    "no program configured",
+                ));
+            }
+            command
+                .to_steps()
+                .into_iter()
+                .find(|step| self.diagnostics.find_executable(&step.program).is_none())
+                .map(|step| {
+                    Check::new(
+                        &label,
+                        CheckStatus::Error,
+                        format!("program '{}' not found on PATH", step.program),
+                    )
+                })
+        }));
+        checks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::{FailureKind, Hooks, NotifyPolicy};
+    use crate::ports::mock::{MockDiagnosticsPort, MockStatePort, MockTerminalPort};
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+
+    fn healthy_diagnostics() -> MockDiagnosticsPort {
+        let mut diagnostics = MockDiagnosticsPort::new();
+        diagnostics
+            .expect_wezterm_version()
+            .returning(|| Ok("wezterm 20240203".to_string()));
+        diagnostics.expect_list_panes().returning(|| Ok(2));
+        diagnostics
+            .expect_current_pane()
+            .returning(|| Some("0".to_string()));
+        diagnostics
+            .expect_config_path()
+            .returning(|| Some(".wez/config.toml".to_string()));
+        diagnostics
+            .expect_check_state_dir()
+            .returning(|| Ok("/state/wez-bits/project".to_string()));
+        diagnostics
+    }
+
+    #[test]
+    fn test_finished_title() {
+        let elapsed = Duration::from_secs(12);
+        assert_eq!(
+            finished_title("test", &ExitStatus::from_raw(0), elapsed, 1),
+            "✔ test (12s)"
+        );
+        assert_eq!(
+            finished_title("test", &ExitStatus::from_raw(101 << 8), elapsed, 1),
+            "✘ test (exit 101)"
+        );
+        assert_eq!(
+            finished_title("test", &ExitStatus::from_raw(9), elapsed, 1),
+            "✘ test (signal 9)"
+        );
+        assert_eq!(
+            finished_title("test", &ExitStatus::from_raw(0), elapsed, 3),
+            "✔ test (12s, 3 attempts)"
+        );
+    }
+
+    #[test]
+    fn test_find_task_resolves_notify_policy() {
+        let service = TaskExecutionService::new(MockTerminalPort::new(), MockStatePort::new());
+        let mut config = ProjectConfig::default();
+        config.settings.notify = NotifyPolicy::After(Duration::from_secs(30));
+        config.tasks.insert("build".to_string(), Command::default());
+        config.tasks.insert(
+            "deploy".to_string(),
+            Command {
+                notify: Some(NotifyPolicy::Always),
+                ..Default::default()
+            },
+        );
+
+        let find = |name: &str| {
+            service
+                .find_task(
+                    name,
+                    &config,
+                    TaskClose::Never,
+                    Direction::Right,
+                    &[],
+                    &BTreeMap::new(),
+                )
+                .unwrap()
+        };
+        assert_eq!(
+            find("build").settings.notify,
+            NotifyPolicy::After(Duration::from_secs(30))
+        );
+        assert_eq!(find("deploy").settings.notify, NotifyPolicy::Always);
+    }
+
+    #[test]
+    fn test_find_task_places_extra_args_in_steps() {
+        let service = TaskExecutionService::new(MockTerminalPort::new(), MockStatePort::new());
+        let step = |program: &str, args: &[&str]| Step {
+            program: program.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            ..Default::default()
+        };
+        let mut config = ProjectConfig::default();
+        config.tasks.insert(
+            "check".to_string(),
+            Command {
+                steps: vec![step("cargo", &["fmt"]), step("cargo", &["test"])],
+                ..Default::default()
+            },
+        );
+        config.tasks.insert(
+            "release".to_string(),
+            Command {
+                steps: vec![step("git", &["tag", "{1}"]), step("git", &["push"])],
+                ..Default::default()
+            },
+        );
+        config.tasks.insert(
+            "both".to_string(),
+            Command {
+                program: "make".to_string(),
+                steps: vec![step("make", &[])],
+                ..Default::default()
+            },
+        );
+        let extra = ["v1".to_string()];
+        let find = |name: &str| {
+            service.find_task(
+                name,
+                &config,
+                TaskClose::Never,
+                Direction::Right,
+                &extra,
+                &BTreeMap::new(),
+            )
+        };
+
+        // Appended to the last step, unless a step has placeholders
+        let steps = find("check").unwrap().command.to_steps();
+        assert_eq!(steps[0].args, ["fmt"]);
+        assert_eq!(steps[1].args, ["test", "v1"]);
+        let steps = find("release").unwrap().command.to_steps();
+        assert_eq!(steps[0].args, ["tag", "v1"]);
+        assert_eq!(steps[1].args, ["push"]);
+
+        let err = find("both").unwrap_err();
+        assert_eq!(FailureKind::from_error(&err), FailureKind::Configuration);
+    }
+
+    #[test]
+    fn test_find_task_runs_scripts_with_their_shell() {
+        let service = TaskExecutionService::new(MockTerminalPort::new(), MockStatePort::new());
+        let mut config = ProjectConfig::default();
+        config.settings.shell = Some("bash -euo pipefail".to_string());
+        config.tasks.insert(
+            "seed".to_string(),
+            Command {
+                script: Some("echo $1".to_string()),
+                ..Default::default()
+            },
+        );
+        config.tasks.insert(
+            "report".to_string(),
+            Command {
+                script: Some("print(1)".to_string()),
+                shell: Some("python3".to_string()),
+                ..Default::default()
+            },
+        );
+        config.tasks.insert(
+            "both".to_string(),
+            Command {
+                program: "make".to_string(),
+                script: Some("make".to_string()),
+                ..Default::default()
+            },
+        );
+        let extra = ["users".to_string()];
+        let find = |name: &str| {
+            service.find_task(
+                name,
+                &config,
+                TaskClose::Never,
+                Direction::Right,
+                &extra,
+                &BTreeMap::new(),
+            )
+        };
+
+        let steps = find("seed").unwrap().command.to_steps();
+        assert_eq!(
+            steps[0].argv("/tmp/seed"),
+            ["bash", "-euo", "pipefail", "/tmp/seed", "users"]
+        );
+        let steps = find("report").unwrap().command.to_steps();
+        assert_eq!(
+            steps[0].argv("/tmp/report"),
+            ["python3", "/tmp/report", "users"]
+        );
+
+        let err = find("both").unwrap_err();
+        assert_eq!(FailureKind::from_error(&err), FailureKind::Configuration);
+    }
+
+    #[test]
+    fn test_expand_args() {
+        let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        let params = BTreeMap::from([
+            ("env".to_string(), "staging".to_string()),
+            ("name".to_string(), "{1}-{args}".to_string()),
+        ]);
+        let expand = |args: &[&str], extra: &[&str]| {
+            expand_args("test", &strings(args), &strings(extra), &params)
+        };
+
+        // Appended when the task has no placeholders
+        assert_eq!(
+            expand(&["test"], &["--nocapture", "my_module"]).unwrap(),
+            ["test", "--nocapture", "my_module"]
+        );
+        assert_eq!(
+            expand(&["test", "{args}", "--", "--nocapture"], &["a", "b"]).unwrap(),
+            ["test", "a", "b", "--", "--nocapture"]
+        );
+        assert_eq!(
+            expand(&["--filter={1}", "{2}", "-m", "{args}x"], &["io", "fast"]).unwrap(),
+            ["--filter=io", "fast", "-m", "io fastx"]
+        );
+        // Other braces are left alone, even without extra arguments
+        assert_eq!(
+            expand(&["{}", "{other}", "${env:HOME}"], &[]).unwrap(),
+            ["{}", "{other}", "${env:HOME}"]
+        );
+        // Param values are placed as they are, placeholders in them included
+        assert_eq!(
+            expand(&["--env={env}", "{name}"], &["io"]).unwrap(),
+            ["--env=staging", "{1}-{args}", "io"]
+        );
+        assert_eq!(
+            expand(&["{name}", "{1}"], &["io"]).unwrap(),
+            ["{1}-{args}", "io"]
+        );
+        assert!(matches!(
+            expand(&["{2}"], &["one"]),
+            Err(DomainError::MissingArgument(_, 2))
+        ));
+    }
+
+    #[test]
+    fn test_notifies_long_running_task() {
+        let mut terminal = MockTerminalPort::new();
+        terminal
+            .expect_open_pane()
+            .returning(|_, _| Ok("1".to_string()));
+        terminal
+            .expect_pipe_text_to_pane()
+            .returning(|_, _| Ok(ExitStatus::from_raw(2 << 8)));
+        terminal.expect_set_pane_title().returning(|_, _| Ok(()));
+        terminal.expect_set_tab_title().returning(|_, _| Ok(()));
+        terminal.expect_set_user_var().returning(|_, _, _| Ok(()));
+        terminal
+            .expect_notify()
+            .withf(|pane, title, body| {
+                pane == "1" && title == "wzb: lint" && body.starts_with("✘ failed with exit 2")
+            })
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        let task = Task::new(
+            "lint",
+            Command::default(),
+            TaskSettings {
+                close: TaskClose::Never,
+                direction: Direction::Down,
+                notify: NotifyPolicy::Always,
+                timeout: None,
+                retry: RetryPolicy::default(),
+                hooks: vec![],
+            },
+        );
+        let mut state = MockStatePort::new();
+        state.expect_record_run().returning(|_| Ok(()));
+        state.expect_append_history().returning(|_| Ok(()));
+
+        let status = TaskExecutionService::new(terminal, state)
+            .execute_task(task)
+            .unwrap();
+        assert_eq!(status.code(), Some(2));
+    }
+
+    #[test]
+    fn test_execute_tasks_stops_after_a_failure_unless_keep_going() {
+        let run = |keep_going: bool, parallel: usize| {
+            let mut terminal = MockTerminalPort::new();
+            terminal
+                .expect_open_pane()
+                .returning(|_, _| Ok("1".to_string()));
+            terminal
+                .expect_pipe_text_to_pane()
+                .returning(|invocation, _| {
+                    let code = if invocation.steps[0].program == "false" {
+                        1
+                    } else {
+                        0
+                    };
+                    Ok(ExitStatus::from_raw(code << 8))
+                });
+            terminal.expect_set_pane_title().returning(|_, _| Ok(()));
+            terminal.expect_set_tab_title().returning(|_, _| Ok(()));
+            terminal.expect_set_user_var().returning(|_, _, _| Ok(()));
+            let mut state = MockStatePort::new();
+            state.expect_record_run().returning(|_| Ok(()));
+            state.expect_append_history().returning(|_| Ok(()));
+
+            let tasks = [("fmt", "true"), ("lint", "false"), ("test", "true")]
+                .into_iter()
+                .map(|(name, program)| {
+                    Task::new(
+                        name,
+                        Command {
+                            program: program.to_string(),
+                            ..Default::default()
+                        },
+                        TaskSettings {
+                            close: TaskClose::Never,
+                            direction: Direction::Right,
+                            notify: NotifyPolicy::Never,
+                            timeout: None,
+                            retry: RetryPolicy::default(),
+                            hooks: vec![],
+                        },
+                    )
+                })
+                .collect();
+            TaskExecutionService::new(terminal, state)
+                .execute_tasks(tasks, keep_going, NonZeroUsize::new(parallel).unwrap())
+                .into_iter()
+                .map(|outcome| {
+                    (
+                        outcome.name,
+                        outcome.result.map(|result| result.unwrap().code()),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let outcome = |name: &str, code: Option<i32>| (name.to_string(), code.map(Some));
+
+        assert_eq!(
+            run(false, 1),
+            [
+                outcome("fmt", Some(0)),
+                outcome("lint", Some(1)),
+                outcome("test", None)
+            ]
+        );
+        let all = [
+            outcome("fmt", Some(0)),
+            outcome("lint", Some(1)),
+            outcome("test", Some(0)),
+        ];
+        assert_eq!(run(true, 1), all);
+        assert_eq!(run(true, 3), all);
+    }
+
+    #[test]
+    fn test_interrupts_task_after_timeout() {
+        let interrupted = Arc::new(AtomicBool::new(false));
+        let mut terminal = MockTerminalPort::new();
+        terminal
+            .expect_open_pane()
+            .returning(|_, _| Ok("1".to_string()));
+        let pane_interrupted = interrupted.clone();
+        terminal
+            .expect_pipe_text_to_pane()
+            .withf(|invocation, _| invocation.timeout == Some(Duration::from_millis(50)))
+            .returning(move |_, _| {
+                while !pane_interrupted.load(Ordering::SeqCst) {
+                    thread::sleep(Duration::from_millis(5));
+                }
+                Ok(ExitStatus::from_raw(130 << 8))
+            });
+        terminal
+            .expect_signal_task()
+            .withf(|pane, signal| pane == "1" && *signal == Signal::Interrupt)
+            .times(1)
+            .returning(move |_, _| {
+                interrupted.store(true, Ordering::SeqCst);
+                Ok(())
+            });
+        terminal
+            .expect_set_pane_title()
+            .withf(|_, title| title.starts_with("⏱ test (timed out after"))
+            .times(1)
+            .returning(|_, _| Ok(()));
+        terminal.expect_set_pane_title().returning(|_, _| Ok(()));
+        terminal.expect_set_tab_title().returning(|_, _| Ok(()));
+        terminal.expect_set_user_var().returning(|_, _, _| Ok(()));
+        // Timing out is a failure, the pane stays open
+
+        let mut state = MockStatePort::new();
+        state.expect_record_run().returning(|_| Ok(()));
+        state
+            .expect_append_history()
+            .withf(|entry| {
+                entry.timed_out
+                    && entry.exit_code == Some(130)
+                    && entry.timeout == Some(Interval(Duration::from_millis(50)))
+            })
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let task = Task::new(
+            "test",
+            Command::default(),
+            TaskSettings {
+                close: TaskClose::OnSuccess,
+                direction: Direction::Right,
+                notify: NotifyPolicy::Never,
+                timeout: Some(Duration::from_millis(50)),
+                retry: RetryPolicy::default(),
+                hooks: vec![],
+            },
+        );
+        let finished = TaskExecutionService::new(terminal, state)
+            .run_task(task)
+            .unwrap();
+        assert_eq!(finished.status.code(), Some(130));
+        assert!(finished.timed_out);
+        assert_eq!(finished.exit_status().code(), Some(124));
+    }
+
+    #[test]
+    fn test_retries_failing_task_in_the_same_pane() {
+        let runs = Arc::new(Mutex::new(vec![]));
+        let mut terminal = MockTerminalPort::new();
+        terminal
+            .expect_open_pane()
+            .times(1)
+            .returning(|_, _| Ok("1".to_string()));
+        let pane_runs = runs.clone();
+        terminal
+            .expect_pipe_text_to_pane()
+            .withf(|_, pane| pane == "1")
+            .returning(move |invocation, _| {
+                let mut runs = pane_runs.lock().unwrap();
+                runs.push(invocation.banner);
+                let code = if runs.len() < 3 { 98 } else { 0 };
+                Ok(ExitStatus::from_raw(code << 8))
+            });
+        terminal
+            .expect_set_pane_title()
+            .withf(|_, title| title.starts_with("✔ e2e (") && title.ends_with(", 3 attempts)"))
+            .times(1)
+            .returning(|_, _| Ok(()));
+        terminal.expect_set_pane_title().returning(|_, _| Ok(()));
+        terminal.expect_set_tab_title().returning(|_, _| Ok(()));
+        terminal.expect_set_user_var().returning(|_, _, _| Ok(()));
+        let mut state = MockStatePort::new();
+        state.expect_record_run().returning(|_| Ok(()));
+        let attempts = Arc::new(Mutex::new(vec![]));
+        let recorded = attempts.clone();
+        state.expect_append_history().returning(move |entry| {
+            recorded
+                .lock()
+                .unwrap()
+                .push((entry.attempt, entry.exit_code));
+            Ok(())
+        });
+
+        let settings = TaskSettings {
+            close: TaskClose::Never,
+            direction: Direction::Right,
+            notify: NotifyPolicy::Never,
+            timeout: None,
+            retry: RetryPolicy {
+                retries: 3,
+                delay: Duration::ZERO,
+                on: vec![98],
+            },
+            hooks: vec![],
+        };
+        let finished = TaskExecutionService::new(terminal, state)
+            .run_task(Task::new("e2e", Command::default(), settings))
+            .unwrap();
+
+        assert!(finished.status.success());
+        assert_eq!(finished.attempts, 3);
+        assert_eq!(
+            *runs.lock().unwrap(),
+            [
+                None,
+                Some("↻ attempt 2/4 after exit 98".to_string()),
+                Some("↻ attempt 3/4 after exit 98".to_string()),
+            ]
+        );
+        assert_eq!(
+            *attempts.lock().unwrap(),
+            [(Some(1), Some(98)), (Some(2), Some(98)), (Some(3), Some(0))]
+        );
+    }
+
+    #[test]
+    fn test_ctrl_c_stops_retries_without_timeout() {
+        let mut terminal = MockTerminalPort::new();
+        terminal
+            .expect_open_pane()
+            .returning(|_, _| Ok("1".to_string()));
+        // The job's shell reports the interrupted task as 130
+        terminal
+            .expect_pipe_text_to_pane()
+            .times(1)
+            .returning(|_, _| Ok(ExitStatus::from_raw(130 << 8)));
+        terminal.expect_set_pane_title().returning(|_, _| Ok(()));
+        terminal.expect_set_tab_title().returning(|_, _| Ok(()));
+        terminal.expect_set_user_var().returning(|_, _, _| Ok(()));
+        let mut state = MockStatePort::new();
+        state.expect_record_run().returning(|_| Ok(()));
+        state
+            .expect_append_history()
+            .withf(|entry| entry.attempt == Some(1) && !entry.timed_out)
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let settings = TaskSettings {
+            close: TaskClose::Never,
+            direction: Direction::Right,
+            notify: NotifyPolicy::Never,
+            timeout: None,
+            retry: RetryPolicy {
+                retries: 3,
+                delay: Duration::ZERO,
+                on: vec![],
+            },
+            hooks: vec![],
+        };
+        let finished = TaskExecutionService::new(terminal, state)
+            .run_task(Task::new("e2e", Command::default(), settings))
+            .unwrap();
+
+        assert_eq!(finished.status.code(), Some(130));
+        assert_eq!(finished.attempts, 1);
+        assert!(!finished.timed_out);
+    }
+
+    #[test]
+    fn test_runs_hooks_around_the_task() {
+        let run = |task_code: i32, before_code: i32| {
+            let ran = Arc::new(Mutex::new(vec![]));
+            let mut terminal = MockTerminalPort::new();
+            terminal
+                .expect_open_pane()
+                .returning(|_, _| Ok("1".to_string()));
+            let pane_ran = ran.clone();
+            terminal
+                .expect_pipe_text_to_pane()
+                .returning(move |invocation, _| {
+                    let env = &invocation.env;
+                    let (ran, code) = match &invocation.steps[0].script {
+                        Some(script) => {
+                            let code = if script.body == "db up" {
+                                before_code
+                            } else {
+                                0
+                            };
+                            let vars = ["WZB_TASK", "WZB_HOOK", "WZB_STATUS", "WZB_EXIT_CODE"]
+                                .map(|name| env.get(name).map_or("-", String::as_str))
+                                .join(" ");
+                            (format!("{}: {}", script.body, vars), code)
+                        }
+                        None => (invocation.steps[0].program.clone(), task_code),
+                    };
+                    pane_ran.lock().unwrap().push(ran);
+                    Ok(ExitStatus::from_raw(code << 8))
+                });
+            terminal.expect_set_pane_title().returning(|_, _| Ok(()));
+            terminal.expect_set_tab_title().returning(|_, _| Ok(()));
+            terminal.expect_set_user_var().returning(|_, _, _| Ok(()));
+            let mut state = MockStatePort::new();
+            state.expect_record_run().returning(|_| Ok(()));
+            state.expect_append_history().returning(|_| Ok(()));
+
+            let hooks = |before: Option<&str>, after: &str, outcome: &str| Hooks {
+                before: before.map(String::from),
+                after: Some(after.to_string()),
+                on_success: Some(format!("{} ok", outcome)),
+                on_failure: Some(format!("{} failed", outcome)),
+            };
+            let settings = TaskSettings {
+                close: TaskClose::Never,
+                direction: Direction::Right,
+                notify: NotifyPolicy::Never,
+                timeout: None,
+                retry: RetryPolicy {
+                    retries: 2,
+                    ..Default::default()
+                },
+                hooks: vec![
+                    hooks(None, "global after", "global"),
+                    hooks(Some("db up"), "db down", "test"),
+                ],
+            };
+            let command = Command {
+                program: "cargo".to_string(),
+                ..Default::default()
+            };
+            let status = TaskExecutionService::new(terminal, state)
+                .execute_task(Task::new("test", command, settings))
+                .unwrap();
+            let ran = ran.lock().unwrap().clone();
+            (status.code(), ran)
+        };
+
+        assert_eq!(
+            run(0, 0),
+            (
+                Some(0),
+                vec![
+                    "db up: test before - -".to_string(),
+                    "cargo".to_string(),
+                    "test ok: test on_success success 0".to_string(),
+                    "global ok: test on_success success 0".to_string(),
+                    "db down: test after success 0".to_string(),
+                    "global after: test after success 0".to_string(),
+                ]
+            )
+        );
+        // A failing before hook skips the task, without retries
+        let (code, ran) = run(0, 7);
+        assert_eq!(code, Some(7));
+        assert_eq!(ran[0], "db up: test before - -");
+        assert_eq!(ran[1], "test failed: test on_failure failed 7");
+        assert!(!ran.contains(&"cargo".to_string()));
+        assert_eq!(ran.len(), 5);
+    }
+
+    #[test]
+    fn test_runs_after_hooks_when_the_task_errors() {
+        let ran = Arc::new(Mutex::new(vec![]));
+        let mut terminal = MockTerminalPort::new();
+        terminal
+            .expect_open_pane()
+            .returning(|_, _| Ok("1".to_string()));
+        let pane_ran = ran.clone();
+        terminal
+            .expect_pipe_text_to_pane()
+            .returning(move |invocation, _| match &invocation.steps[0].script {
+                Some(script) => {
+                    let env = &invocation.env;
+                    pane_ran.lock().unwrap().push(format!(
+                        "{}: {} {}",
+                        script.body,
+                        env["WZB_STATUS"],
+                        env.get("WZB_EXIT_CODE").map_or("-", String::as_str)
+                    ));
+                    Ok(ExitStatus::from_raw(0))
+                }
+                None => Err(anyhow!("pane closed")),
+            });
+        terminal.expect_set_pane_title().returning(|_, _| Ok(()));
+        terminal.expect_set_tab_title().returning(|_, _| Ok(()));
+        terminal.expect_set_user_var().returning(|_, _, _| Ok(()));
+        let mut state = MockStatePort::new();
+        state.expect_record_run().returning(|_| Ok(()));
+        state.expect_append_history().returning(|_| Ok(()));
+
+        let settings = TaskSettings {
+            close: TaskClose::Never,
+            direction: Direction::Right,
+            notify: NotifyPolicy::Never,
+            timeout: None,
+            retry: RetryPolicy::default(),
+            hooks: vec![Hooks {
+                after: Some("db down".to_string()),
+                on_failure: Some("alert".to_string()),
+                ..Default::default()
+            }],
+        };
+        let command = Command {
+            program: "cargo".to_string(),
+            ..Default::default()
+        };
+        let result = TaskExecutionService::new(terminal, state)
+            .execute_task(Task::new("test", command, settings));
+
+        assert!(result.is_err());
+        assert_eq!(
+            *ran.lock().unwrap(),
+            ["alert: failed -", "db down: failed -"]
+        );
+    }
+
+    #[test]
+    fn test_publishes_task_state() {
+        let mut terminal = MockTerminalPort::new();
+        terminal
+            .expect_open_pane()
+            .returning(|_, _| Ok("7".to_string()));
+        terminal
+            .expect_pipe_text_to_pane()
+            .returning(|_, _| Ok(ExitStatus::from_raw(0)));
+        terminal.expect_set_pane_title().returning(|_, _| Ok(()));
+        terminal.expect_set_tab_title().returning(|_, _| Ok(()));
+        terminal.expect_close_pane().returning(|_| Ok(()));
+        terminal
+            .expect_set_user_var()
+            .withf(|pane, name, value| pane == "7" && name == "wzb_status" && value == "running")
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        terminal
+            .expect_set_user_var()
+            .withf(|_, name, value| name == "wzb_status" && value == "success")
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        terminal
+            .expect_set_user_var()
+            .withf(|_, name, _| name != "wzb_status")
+            .returning(|_, _, _| Ok(()));
+
+        let mut state = MockStatePort::new();
+        state
+            .expect_record_run()
+            .withf(|run| run.status == RunStatus::Running && run.exit_code.is_none())
+            .times(1)
+            .returning(|_| Ok(()));
+        state
+            .expect_record_run()
+            .withf(|run| run.status == RunStatus::Success && run.exit_code == Some(0))
+            .times(1)
+            .returning(|_| Err(anyhow!("state is optional")));
+        state
+            .expect_append_history()
+            .withf(|entry| {
+                entry.name == "test"
+                    && entry.exit_code == Some(0)
+                    && entry.close == TaskClose::OnSuccess
+            })
+            .times(1)
+            .returning(|_| Err(anyhow!("history is optional")));
+
+        let task = Task::new(
+            "test",
+            Command::default(),
+            TaskSettings {
+                close: TaskClose::OnSuccess,
+                direction: Direction::Right,
+                notify: NotifyPolicy::Never,
+                timeout: None,
+                retry: RetryPolicy::default(),
+                hooks: vec![],
+            },
+        );
+        let status = TaskExecutionService::new(terminal, state)
+            .execute_task(task)
+            .unwrap();
+        assert!(status.success());
+    }
+
+    #[test]
+    fn test_task_list() {
+        let mut config = ProjectConfig::default();
+        let mut add = |name: &str, group: Option<&str>, description: Option<&str>| {
+            config.tasks.insert(
+                name.to_string(),
+                Command {
+                    program: "cargo".to_string(),
+                    args: vec![name.to_string()],
+                    group: group.map(str::to_string),
+                    description: description.map(str::to_string),
+                    ..Default::default()
+                },
+            );
+        };
+        add("build", Some("common"), Some("Build everything"));
+        add("test", Some("common"), None);
+        add("q", Some("registers"), None);
+        add("release", None, Some("Tag and publish"));
+        add("_setup", Some("common"), None);
+
+        assert_eq!(
+            task_list(&config, false),
+            "common:\n\
+             \x20 build    Build everything\n\
+             \x20 test     cargo test\n\
+             registers:\n\
+             \x20 q        cargo q\n\
+             other:\n\
+             \x20 release  Tag and publish"
+        );
+        assert!(task_list(&config, true).contains("  _setup   cargo _setup (hidden)"));
+
+        // Without any group the list stays flat
+        let mut flat = ProjectConfig::default();
+        flat.tasks.insert("lint".to_string(), Command::default());
+        flat.tasks.insert(
+            "fmt".to_string(),
+            Command {
+                hidden: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(task_list(&flat, false), "lint  (no program)");
+    }
+
+    #[test]
+    fn test_in_namespace() {
+        let mut config = ProjectConfig::default();
+        for name in ["rust:build", "rust:test", "web:build", "rustfmt"] {
+            config.tasks.insert(name.to_string(), Command::default());
         }
+
+        let rust = in_namespace(&config, "rust:").unwrap();
+        assert_eq!(
+            rust.tasks.keys().collect::<Vec<_>>(),
+            ["rust:build", "rust:test"]
+        );
+        assert!(matches!(
+            in_namespace(&config, "go:"),
+            Err(DomainError::FeatureNotConfigured(_))
+        ));
+    }
+
+    #[test]
+    fn test_resolve_task_name() {
+        let mut config = ProjectConfig::default();
+        for name in ["build", "bench", "q", "Q", "_setup"] {
+            config.tasks.insert(name.to_string(), Command::default());
+        }
+        config.tasks.insert(
+            "test".to_string(),
+            Command {
+                aliases: vec!["t".to_string()],
+                ..Default::default()
+            },
+        );
+
+        let resolve = |name: &str| resolve_task_name(&config, name);
+        assert_eq!(resolve("Q").unwrap(), "Q");
+        assert_eq!(resolve("t").unwrap(), "test");
+        assert_eq!(resolve("bu").unwrap(), "build");
+        assert_eq!(resolve("_setup").unwrap(), "_setup");
+        assert!(matches!(resolve("_s"), Err(DomainError::UnknownTask(..))));
+        assert!(matches!(
+            resolve("b"),
+            Err(DomainError::AmbiguousTask(_, candidates)) if candidates == ["bench", "build"]
+        ));
+        assert_eq!(
+            resolve("biuld").unwrap_err().to_string(),
+            "Task 'biuld' not configured, did you mean 'build'?"
+        );
+        assert_eq!(
+            resolve("besth").unwrap_err().to_string(),
+            "Task 'besth' not configured, did you mean 'bench' or 'test'?"
+        );
+        assert_eq!(
+            resolve("deploy").unwrap_err().to_string(),
+            "Task 'deploy' not configured"
+        );
+    }
+
+    #[test]
+    fn test_run_summary() {
+        let outcome = |name: &str, result: Option<Result<ExitStatus>>, secs: u64| TaskOutcome {
+            name: name.to_string(),
+            result,
+            elapsed: Duration::from_secs(secs),
+            timed_out: false,
+            attempts: 1,
+        };
+        let outcomes = [
+            outcome("fmt", Some(Ok(ExitStatus::from_raw(0))), 2),
+            TaskOutcome {
+                attempts: 3,
+                ..outcome("lint", Some(Ok(ExitStatus::from_raw(1 << 8))), 75)
+            },
+            outcome("deploy", Some(Err(anyhow!("pane closed"))), 0),
+            outcome("test", None, 0),
+        ];
+
+        assert_eq!(
+            run_summary(&outcomes),
+            [
+                "fmt     ✔ success  2s",
+                "lint    ✘ exit 1   1m 15s, 3 attempts",
+                "deploy  ✘ error    pane closed",
+                "test    - not run",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_history_list() {
+        let entry = |name: &str, started_at: u64, exit_code: Option<i32>| HistoryEntry {
+            name: name.to_string(),
+            command: format!("make {}", name),
+            cwd: None,
+            started_at,
+            duration_ms: 2500,
+            exit_code,
+            timed_out: false,
+            attempt: None,
+            timeout: None,
+            pane_id: "1".to_string(),
+            close: TaskClose::OnSuccess,
+            direction: Direction::Right,
+            extra_args: vec![],
+            params: BTreeMap::new(),
+        };
+        let entries = [
+            entry("build", 1_000, Some(0)),
+            HistoryEntry {
+                attempt: Some(2),
+                ..entry("test", 1_290, Some(2))
+            },
+            entry("q", 1_300, None),
+        ];
+
+        assert_eq!(
+            history_list(&entries, 1_300),
+            [
+                " 5m 00s ago  build  ✔          2s  make build",
+                "    10s ago  test   ✘ 2        2s  make test  (attempt 2)",
+                "        now  q      ✘          2s  make q",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_statusline() {
+        let run = |name: &str, status: RunStatus| TaskRun {
+            name: name.to_string(),
+            status,
+            pane_id: "1".to_string(),
+            pid: 1,
+            started_at: 0,
+            duration_ms: None,
+            exit_code: None,
+        };
+
+        assert_eq!(statusline(&[]), "");
+        assert_eq!(
+            statusline(&[
+                run("build", RunStatus::Running),
+                run("fmt", RunStatus::Success),
+                run("lint", RunStatus::Failed),
+                run("test", RunStatus::Running),
+            ]),
+            "▶ build test ✘ lint"
+        );
+    }
+
+    #[test]
+    fn test_all_checks_pass() {
+        let mut diagnostics = healthy_diagnostics();
+        diagnostics
+            .expect_find_executable()
+            .returning(|program| Some(format!("/usr/bin/{}", program)));
+
+        let mut config = ProjectConfig::default();
+        config.tasks.insert(
+            "build".to_string(),
+            Command {
+                program: "cargo".to_string(),
+                args: vec!["build".to_string()],
+                ..Default::default()
+            },
+        );
+
+        let checks =
+            DiagnosticsService::new(diagnostics).run_checks(BackendKind::WezTerm, Ok(config));
+        assert!(checks.iter().all(|check| check.status == CheckStatus::Ok));
+        assert!(checks[0].detail.contains("wezterm 20240203"));
+    }
+
+    #[test]
+    fn test_missing_wezterm_and_task_programs() {
+        let mut diagnostics = healthy_diagnostics();
+        diagnostics.expect_find_executable().returning(|_| None);
+
+        let mut config = ProjectConfig::default();
+        config.tasks.insert(
+            "build".to_string(),
+            Command {
+                program: "npm".to_string(),
+                args: vec![],
+                ..Default::default()
+            },
+        );
+        config.tasks.insert(
+            "q".to_string(),
+            Command {
+                program: "".to_string(),
+                args: vec![],
+                ..Default::default()
+            },
+        );
+
+        let checks =
+            DiagnosticsService::new(diagnostics).run_checks(BackendKind::WezTerm, Ok(config));
+        let find = |name: &str| checks.iter().find(|check| check.name == name).unwrap();
+
+        assert_eq!(find("wezterm").status, CheckStatus::Error);
+        assert_eq!(find("wezterm").failure_kind(), FailureKind::Terminal);
+        assert_eq!(find("task build").status, CheckStatus::Error);
+        assert!(find("task build").detail.contains("npm"));
+        assert_eq!(find("task q").status, CheckStatus::Warning);
+    }
+
+    #[test]
+    fn test_checks_follow_the_backend() {
+        let mut diagnostics = MockDiagnosticsPort::new();
+        diagnostics
+            .expect_find_executable()
+            .returning(|program| (program == "tmux").then(|| "/usr/bin/tmux".to_string()));
+        diagnostics
+            .expect_config_path()
+            .returning(|| Some(".wez/config.toml".to_string()));
+        diagnostics
+            .expect_check_state_dir()
+            .returning(|| Ok("/tmp/wez-bits".to_string()));
+        let service = DiagnosticsService::new(diagnostics);
+        let names = |backend| {
+            service
+                .run_checks(backend, Ok(ProjectConfig::default()))
+                .into_iter()
+                .map(|check| (check.name, check.status))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names(BackendKind::Tmux),
+            [
+                ("tmux".to_string(), CheckStatus::Ok),
+                ("config".to_string(), CheckStatus::Ok),
+                ("state directory".to_string(), CheckStatus::Ok),
+            ]
+        );
+        assert_eq!(
+            names(BackendKind::Local),
+            [
+                ("config".to_string(), CheckStatus::Ok),
+                ("state directory".to_string(), CheckStatus::Ok),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let mut diagnostics = healthy_diagnostics();
+        diagnostics
+            .expect_find_executable()
+            .returning(|program| Some(program.to_string()));
+
+        let checks = DiagnosticsService::new(diagnostics).run_checks(
+            BackendKind::WezTerm,
+            Err(anyhow!("expected `=`, found newline")),
+        );
+        let config = checks.iter().find(|check| check.name == "config").unwrap();
+
+        assert_eq!(config.status, CheckStatus::Error);
+        assert_eq!(config.failure_kind(), FailureKind::Configuration);
+        assert!(config.detail.contains("expected `=`"));
     }
 }
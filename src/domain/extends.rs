@@ -0,0 +1,215 @@
+//! Task inheritance through `extends`, resolved once the config is parsed
+
+use std::collections::BTreeMap;
+
+use crate::domain::models::{Command, ConfigError, ProjectConfig};
+
+/// Merge every task with the tasks it `extends`, parents first
+pub fn resolve_extends(mut config: ProjectConfig) -> Result<ProjectConfig, ConfigError> {
+    let mut resolved = BTreeMap::new();
+    for name in config.tasks.keys() {
+        resolve(name, &config.tasks, &mut resolved, &mut vec![])?;
+    }
+    config.tasks = resolved;
+    Ok(config)
+}
+
+fn resolve(
+    name: &str,
+    tasks: &BTreeMap<String, Command>,
+    resolved: &mut BTreeMap<String, Command>,
+    chain: &mut Vec<String>,
+) -> Result<Command, ConfigError> {
+    if let Some(command) = resolved.get(name) {
+        return Ok(command.clone());
+    }
+    if chain.iter().any(|task| task == name) {
+        chain.push(name.to_string());
+        return Err(ConfigError::Extends(format!(
+            "tasks extend each other in a cycle: {}",
+            chain.join(" -> ")
+        )));
+    }
+
+    let command = &tasks[name];
+    let mut merged = match &command.extends {
+        None => command.clone(),
+        Some(parent) => {
+            if !tasks.contains_key(parent) {
+                return Err(ConfigError::Extends(format!(
+                    "task '{}' extends '{}', which is not configured",
+                    name, parent
+                )));
+            }
+            chain.push(name.to_string());
+            let parent = resolve(parent, tasks, resolved, chain)?;
+            chain.pop();
+            inherit(command, &parent)
+        }
+    };
+    merged.args.append(&mut merged.append_args);
+
+    resolved.insert(name.to_string(), merged.clone());
+    Ok(merged)
+}
+
+/// `child` with the unset parts of what to run and how filled in from `parent`.
+/// Its key, description, icon, group, aliases and visibility stay its own.
+fn inherit(child: &Command, parent: &Command) -> Command {
+    let mut merged = child.clone();
+
+    // Program, steps and script replace each other rather than mix
+    let runs_own = !child.program.is_empty() || !child.steps.is_empty() || child.script.is_some();
+    if !runs_own {
+        merged.program = parent.program.clone();
+        merged.steps = parent.steps.clone();
+        merged.script = parent.script.clone();
+    }
+    if child.args.is_empty() {
+        merged.args = parent.args.clone();
+    }
+    merged.shell = child.shell.clone().or(parent.shell.clone());
+    merged.cwd = child.cwd.clone().or(parent.cwd.clone());
+    merged.notify = child.notify.or(parent.notify);
+    merged.timeout = child.timeout.or(parent.timeout);
+    merged.retries = child.retries.or(parent.retries);
+    merged.retry_delay = child.retry_delay.or(parent.retry_delay);
+    if child.retry_on.is_empty() {
+        merged.retry_on = parent.retry_on.clone();
+    }
+    merged.hooks = child.hooks.or(&parent.hooks);
+    if child.params.is_empty() {
+        merged.params = parent.params.clone();
+    }
+
+    merged.env = parent.env.clone();
+    merged.env.extend(child.env.clone());
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::{FailureKind, Hooks};
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn config(tasks: Vec<(&str, Command)>) -> ProjectConfig {
+        let mut config = ProjectConfig::default();
+        for (name, command) in tasks {
+            config.tasks.insert(name.to_string(), command);
+        }
+        config
+    }
+
+    #[test]
+    fn test_children_override_and_append() {
+        let config = config(vec![
+            (
+                "_cargo",
+                Command {
+                    program: "cargo".to_string(),
+                    args: strings(&["build", "--workspace"]),
+                    env: BTreeMap::from([
+                        ("RUST_LOG".to_string(), "info".to_string()),
+                        ("CARGO_TERM_COLOR".to_string(), "always".to_string()),
+                    ]),
+                    cwd: Some("backend".to_string()),
+                    hidden: true,
+                    hooks: Hooks {
+                        before: Some("make proto".to_string()),
+                        after: Some("make clean".to_string()),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            ),
+            (
+                "release",
+                Command {
+                    extends: Some("_cargo".to_string()),
+                    append_args: strings(&["--release"]),
+                    env: BTreeMap::from([("RUST_LOG".to_string(), "warn".to_string())]),
+                    hooks: Hooks {
+                        after: Some("du -sh target".to_string()),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            ),
+            (
+                "check",
+                Command {
+                    extends: Some("release".to_string()),
+                    args: strings(&["check"]),
+                    ..Default::default()
+                },
+            ),
+        ]);
+
+        let config = resolve_extends(config).unwrap();
+        let release = &config.tasks["release"];
+        assert_eq!(release.program, "cargo");
+        assert_eq!(release.args, ["build", "--workspace", "--release"]);
+        assert_eq!(release.env["RUST_LOG"], "warn");
+        assert_eq!(release.env["CARGO_TERM_COLOR"], "always");
+        assert_eq!(release.cwd.as_deref(), Some("backend"));
+        assert!(!release.hidden);
+        assert_eq!(release.hooks.before.as_deref(), Some("make proto"));
+        assert_eq!(release.hooks.after.as_deref(), Some("du -sh target"));
+
+        // Grandchildren inherit through their parent, own args replace inherited ones
+        let check = &config.tasks["check"];
+        assert_eq!(check.program, "cargo");
+        assert_eq!(check.args, ["check"]);
+        assert_eq!(check.env["RUST_LOG"], "warn");
+    }
+
+    #[test]
+    fn test_own_script_replaces_inherited_program() {
+        let config = config(vec![
+            (
+                "base",
+                Command {
+                    program: "make".to_string(),
+                    cwd: Some("web".to_string()),
+                    ..Default::default()
+                },
+            ),
+            (
+                "snippet",
+                Command {
+                    extends: Some("base".to_string()),
+                    script: Some("echo hi".to_string()),
+                    ..Default::default()
+                },
+            ),
+        ]);
+
+        let snippet = &resolve_extends(config).unwrap().tasks["snippet"];
+        assert_eq!(snippet.program, "");
+        assert_eq!(snippet.cwd.as_deref(), Some("web"));
+    }
+
+    #[test]
+    fn test_cycles_and_unknown_parents_are_config_errors() {
+        let extends = |parent: &str| Command {
+            extends: Some(parent.to_string()),
+            ..Default::default()
+        };
+
+        let err = resolve_extends(config(vec![
+            ("a", extends("b")),
+            ("b", extends("c")),
+            ("c", extends("a")),
+        ]))
+        .unwrap_err();
+        assert!(err.to_string().contains("a -> b -> c -> a"), "{}", err);
+        assert_eq!(err.failure_kind(), FailureKind::Configuration);
+
+        let err = resolve_extends(config(vec![("a", extends("missing"))])).unwrap_err();
+        assert!(err.to_string().contains("task 'a' extends 'missing'"));
+    }
+}
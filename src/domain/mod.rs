@@ -1,2 +1,6 @@
 pub mod behaviours;
+pub mod extends;
 pub mod models;
+pub mod params;
+pub mod picker;
+pub mod variables;
@@ -1,6 +1,12 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fmt::Display;
+use std::os::unix::process::ExitStatusExt;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::slice;
+use std::str::FromStr;
+use std::time::Duration;
 use thiserror::Error;
 
 /// Domain-specific errors in the application
@@ -12,11 +18,50 @@ pub enum DomainError {
     #[error("Task '{0}' not configured")]
     FeatureNotConfigured(String),
 
+    #[error("Task '{0}' not configured{}", did_you_mean(.1))]
+    UnknownTask(String, Vec<String>),
+
+    #[error("Task '{0}' is ambiguous, it could be {}", .1.join(", "))]
+    AmbiguousTask(String, Vec<String>),
+
+    #[error("No task has run in this project yet")]
+    NoHistory,
+
     #[error("File operation failed: {0}")]
     FileOperation(String),
 
     #[error("Terminal operation failed: {0}")]
     TerminalOperation(String),
+
+    #[error("No task name given and stdin is not a terminal to pick one from")]
+    MissingTaskName,
+
+    #[error("Task '{0}' needs argument {{{1}}}, pass it after `--`")]
+    MissingArgument(String, usize),
+
+    #[error("Task '{0}' needs parameter '{1}', pass it with `--param {1}=VALUE`")]
+    MissingParam(String, String),
+
+    #[error("Task '{0}' has no parameter '{1}'")]
+    UnknownParam(String, String),
+
+    #[error("Invalid value for parameter '{1}' of task '{0}': {2}")]
+    InvalidParam(String, String, String),
+}
+
+fn did_you_mean(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        [only] => format!(", did you mean '{}'?", only),
+        [rest @ .., last] => format!(
+            ", did you mean {} or '{}'?",
+            rest.iter()
+                .map(|name| format!("'{}'", name))
+                .collect::<Vec<_>>()
+                .join(", "),
+            last
+        ),
+    }
 }
 
 /// File system related errors
@@ -43,6 +88,12 @@ pub enum ConfigError {
 
     #[error("Failed to create default configuration: {0}")]
     Create(String),
+
+    #[error("Failed to expand variables: {0}")]
+    Variable(String),
+
+    #[error("Failed to resolve `extends`: {0}")]
+    Extends(String),
 }
 
 /// Terminal operation errors
@@ -56,6 +107,109 @@ pub enum TerminalError {
 
     #[error("Failed to pipe text to pane: {0}")]
     PipeText(String),
+
+    #[error("Failed to set title: {0}")]
+    Title(String),
+
+    #[error("Failed to send notification: {0}")]
+    Notify(String),
+
+    #[error("Failed to set user variable: {0}")]
+    UserVar(String),
+
+    #[error("Failed to signal task: {0}")]
+    Signal(String),
+}
+
+/// Why wez bits itself failed, each reason maps to a distinct exit code.
+///
+/// Codes follow `sysexits.h` so they stay clear of the common task exit
+/// codes, which are propagated verbatim when a task fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// The command line is incomplete (64)
+    Usage,
+    /// The requested task is not in the config file (65)
+    TaskNotConfigured,
+    /// The terminal backend could not open, drive or close a pane (69)
+    Terminal,
+    /// Anything not covered by a more specific kind (70)
+    Internal,
+    /// Reading or writing files failed (74)
+    FileSystem,
+    /// The config file is missing or invalid (78)
+    Configuration,
+    /// A task outlived its timeout (124), like coreutils `timeout`
+    TimedOut,
+}
+
+impl FailureKind {
+    pub fn exit_code(self) -> u8 {
+        match self {
+            FailureKind::Usage => 64,
+            FailureKind::TaskNotConfigured => 65,
+            FailureKind::Terminal => 69,
+            FailureKind::Internal => 70,
+            FailureKind::FileSystem => 74,
+            FailureKind::Configuration => 78,
+            FailureKind::TimedOut => 124,
+        }
+    }
+
+    /// Classify an error by the most specific domain error it carries
+    pub fn from_error(err: &anyhow::Error) -> Self {
+        if let Some(err) = err.downcast_ref::<ConfigError>() {
+            return err.failure_kind();
+        }
+        if let Some(err) = err.downcast_ref::<TerminalError>() {
+            return err.failure_kind();
+        }
+        if let Some(err) = err.downcast_ref::<FileSystemError>() {
+            return err.failure_kind();
+        }
+        if let Some(err) = err.downcast_ref::<DomainError>() {
+            return err.failure_kind();
+        }
+        FailureKind::Internal
+    }
+}
+
+impl DomainError {
+    pub fn failure_kind(&self) -> FailureKind {
+        match self {
+            DomainError::Configuration(_) => FailureKind::Configuration,
+            DomainError::FeatureNotConfigured(_) | DomainError::UnknownTask(..) => {
+                FailureKind::TaskNotConfigured
+            }
+            DomainError::FileOperation(_) => FailureKind::FileSystem,
+            DomainError::TerminalOperation(_) => FailureKind::Terminal,
+            DomainError::MissingTaskName
+            | DomainError::AmbiguousTask(..)
+            | DomainError::NoHistory
+            | DomainError::MissingArgument(..)
+            | DomainError::MissingParam(..)
+            | DomainError::UnknownParam(..)
+            | DomainError::InvalidParam(..) => FailureKind::Usage,
+        }
+    }
+}
+
+impl FileSystemError {
+    pub fn failure_kind(&self) -> FailureKind {
+        FailureKind::FileSystem
+    }
+}
+
+impl ConfigError {
+    pub fn failure_kind(&self) -> FailureKind {
+        FailureKind::Configuration
+    }
+}
+
+impl TerminalError {
+    pub fn failure_kind(&self) -> FailureKind {
+        FailureKind::Terminal
+    }
 }
 
 // From implementations for error conversions
@@ -89,33 +243,847 @@ impl From<toml::de::Error> for ConfigError {
     }
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
 pub struct Command {
+    #[serde(default)]
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+
+    /// Task whose program, args, env, cwd and params this one inherits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+
+    /// Added after `args`, including inherited ones
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub append_args: Vec<String>,
+
+    /// Programs run in order instead of `program`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<Step>,
+
+    /// Inline script run from a temp file instead of `program`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+
+    /// Interpreter of `script` with its flags, e.g. `bash -euo pipefail`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+
+    /// Overrides `settings.notify` for this task
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notify: Option<NotifyPolicy>,
+
+    /// How long the task may run before it is interrupted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<Interval>,
+
+    /// How many times a failing task runs again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+
+    /// Pause before each retry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_delay: Option<Interval>,
+
+    /// Exit codes worth a retry, any failure when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retry_on: Vec<i32>,
+
+    /// Commands run in the task pane around the task
+    #[serde(flatten)]
+    pub hooks: Hooks,
+
+    /// Other names the task runs by, e.g. `t` for `test`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+
+    /// WezTerm key binding running this task, e.g. `ctrl-b`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+
+    /// One line summary shown by exports such as the command palette
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// WezTerm Nerd Font icon name, e.g. `md_hammer`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+
+    /// Working directory, relative paths start at the project root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+
+    /// Environment variables set for the task
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+
+    /// Inputs substituted into args and env as `{name}`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<Param>,
+
+    /// Heading the task is listed under, e.g. `registers`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+
+    /// Keep the task out of `wzb task list`, the picker and exports
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hidden: bool,
+}
+
+impl Command {
+    /// Hidden tasks and `_` prefixed helpers are only run by name
+    pub fn is_hidden(&self, name: &str) -> bool {
+        self.hidden || name.starts_with('_')
+    }
+
+    /// Whether there is a program, steps or a script to run
+    pub fn is_runnable(&self) -> bool {
+        !self.program.is_empty() || !self.steps.is_empty() || self.script.is_some()
+    }
+
+    /// What the task runs, its `program` or `script` as a single step unless
+    /// it has `steps`
+    pub fn to_steps(&self) -> Vec<Step> {
+        if !self.steps.is_empty() {
+            return self.steps.clone();
+        }
+        if let Some(body) = &self.script {
+            let shell = self.shell.as_deref().unwrap_or(DEFAULT_SHELL);
+            let mut words = shell.split_whitespace().map(String::from);
+            return vec![Step {
+                program: words.next().unwrap_or_else(|| DEFAULT_SHELL.to_string()),
+                args: words.collect(),
+                script: Some(Script {
+                    body: body.clone(),
+                    args: self.args.clone(),
+                    path: None,
+                }),
+                ..Default::default()
+            }];
+        }
+        vec![Step {
+            program: self.program.clone(),
+            args: self.args.clone(),
+            ..Default::default()
+        }]
+    }
+
+    /// Program and args, or every step joined with `&&`
+    pub fn command_line(&self) -> String {
+        self.to_steps()
+            .iter()
+            .map(Step::command_line)
+            .collect::<Vec<_>>()
+            .join(" && ")
+    }
+}
+
+/// One program of a multi-step task
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Step {
     pub program: String,
+    #[serde(default)]
     pub args: Vec<String>,
+
+    /// Run the following steps even when this one fails
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub continue_on_error: bool,
+
+    /// Set for `script` tasks, which aren't configured as steps
+    #[serde(skip)]
+    pub script: Option<Script>,
+}
+
+/// Body of a `script` task and the arguments it receives as `$1`, `$2`...
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Script {
+    pub body: String,
+    pub args: Vec<String>,
+    /// Private temp file the body was written to before running
+    pub path: Option<PathBuf>,
+}
+
+impl Step {
+    pub fn command_line(&self) -> String {
+        self.argv("<script>").join(" ")
+    }
+
+    /// Program and args, followed for scripts by `script_file` and the script's args
+    pub fn argv(&self, script_file: &str) -> Vec<String> {
+        let mut argv = [slice::from_ref(&self.program), self.args.as_slice()].concat();
+        if let Some(script) = &self.script {
+            argv.push(script_file.to_string());
+            argv.extend(script.args.iter().cloned());
+        }
+        argv
+    }
+
+    /// `argv` running a script from the temp file it was written to
+    pub fn written_argv(&self) -> Vec<String> {
+        let path = self
+            .script
+            .as_ref()
+            .and_then(|script| script.path.as_ref())
+            .map(|path| path.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.argv(&path)
+    }
+
+    /// Line shown in the pane before step `number` of `count` runs
+    pub fn header(&self, number: usize, count: usize) -> String {
+        format!("▶ [{}/{}] {}", number, count, self.command_line())
+    }
+}
+
+/// An input a task asks for, given with `--param` or at a prompt
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Param {
+    pub name: String,
+
+    #[serde(default, rename = "type")]
+    pub kind: ParamType,
+
+    /// The only values accepted, offered as a list at the prompt
+    #[serde(
+        default,
+        deserialize_with = "scalars",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub choices: Vec<String>,
+
+    /// Used when the value isn't given and nobody can be asked
+    #[serde(
+        default,
+        deserialize_with = "scalar",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub default: Option<String>,
+}
+
+/// What a parameter value has to parse as
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamType {
+    #[default]
+    String,
+    Int,
+    Float,
+    Bool,
+}
+
+impl Display for ParamType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParamType::String => write!(f, "string"),
+            ParamType::Int => write!(f, "int"),
+            ParamType::Float => write!(f, "float"),
+            ParamType::Bool => write!(f, "bool"),
+        }
+    }
+}
+
+/// TOML scalar read as the text it would be given as on the command line
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Scalar {
+    String(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl From<Scalar> for String {
+    fn from(scalar: Scalar) -> Self {
+        match scalar {
+            Scalar::String(value) => value,
+            Scalar::Int(value) => value.to_string(),
+            Scalar::Float(value) => value.to_string(),
+            Scalar::Bool(value) => value.to_string(),
+        }
+    }
+}
+
+fn scalar<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(Option::<Scalar>::deserialize(deserializer)?.map(String::from))
+}
+
+fn scalars<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    Ok(Vec::<Scalar>::deserialize(deserializer)?
+        .into_iter()
+        .map(String::from)
+        .collect())
+}
+
+/// Interpreter of `script` tasks without a `shell`
+pub const DEFAULT_SHELL: &str = "sh";
+
+/// A program with its arguments, ready to run in a task pane
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Invocation {
+    /// Programs with their arguments, run in order with a header each when
+    /// there are several
+    pub steps: Vec<Step>,
+    /// Directory to run in, the pane's own directory when unset
+    pub cwd: Option<String>,
+    /// Variables added to the environment the steps run in
+    pub env: BTreeMap<String, String>,
+    /// Set when the task may be stopped with `TerminalPort::signal_task`
+    pub timeout: Option<Duration>,
+    /// Line printed before the steps, e.g. the attempt of a retried task
+    pub banner: Option<String>,
 }
 
 #[derive(Debug, Clone)]
 pub struct TaskSettings {
     pub close: TaskClose,
     pub direction: Direction,
+    pub notify: NotifyPolicy,
+    pub timeout: Option<Duration>,
+    pub retry: RetryPolicy,
+    /// Hooks from `[settings]` and the task, outermost first
+    pub hooks: Vec<Hooks>,
+}
+
+/// Shell commands run in the task pane before and after a task, with
+/// `WZB_*` variables describing the task
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Hooks {
+    /// Run before the task, a failure skips the task
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+
+    /// Run once the task finished, whatever its outcome
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_success: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_failure: Option<String>,
+}
+
+impl Hooks {
+    pub fn get(&self, kind: HookKind) -> Option<&str> {
+        match kind {
+            HookKind::Before => self.before.as_deref(),
+            HookKind::After => self.after.as_deref(),
+            HookKind::OnSuccess => self.on_success.as_deref(),
+            HookKind::OnFailure => self.on_failure.as_deref(),
+        }
+    }
+
+    /// These hooks, with the unset ones taken from `parent`
+    pub fn or(&self, parent: &Hooks) -> Hooks {
+        Hooks {
+            before: self.before.clone().or(parent.before.clone()),
+            after: self.after.clone().or(parent.after.clone()),
+            on_success: self.on_success.clone().or(parent.on_success.clone()),
+            on_failure: self.on_failure.clone().or(parent.on_failure.clone()),
+        }
+    }
+}
+
+/// When a hook runs, named like its setting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookKind {
+    Before,
+    After,
+    OnSuccess,
+    OnFailure,
+}
+
+impl Display for HookKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            HookKind::Before => write!(f, "before"),
+            HookKind::After => write!(f, "after"),
+            HookKind::OnSuccess => write!(f, "on_success"),
+            HookKind::OnFailure => write!(f, "on_failure"),
+        }
+    }
+}
+
+/// When a failed task runs again, in the same pane
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub delay: Duration,
+    /// Exit codes worth a retry, any failure when empty
+    pub on: Vec<i32>,
+}
+
+impl RetryPolicy {
+    /// Attempts at most, the first run included
+    pub fn attempts(&self) -> u32 {
+        self.retries.saturating_add(1)
+    }
+
+    /// Whether a failed `attempt` ending with `status` is followed by another.
+    /// A task interrupted or terminated other than by its timeout was stopped
+    /// on purpose, e.g. with Ctrl-C, and only runs again when `on` lists its code.
+    pub fn should_retry(&self, attempt: u32, status: &ExitStatus, timed_out: bool) -> bool {
+        if attempt >= self.attempts() {
+            return false;
+        }
+        if let Some(code) = status.code() {
+            if self.on.contains(&code) {
+                return true;
+            }
+        }
+        let interrupted = [Signal::Interrupt, Signal::Terminate].iter().any(|signal| {
+            status.code() == Some(128 + signal.number()) || status.signal() == Some(signal.number())
+        });
+        self.on.is_empty() && (timed_out || !interrupted)
+    }
+}
+
+/// Signals sent to a task that outlived its timeout, in escalation order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Interrupt,
+    Terminate,
+    Kill,
+}
+
+impl Signal {
+    /// Name as `kill` takes it
+    pub fn name(&self) -> &'static str {
+        match self {
+            Signal::Interrupt => "INT",
+            Signal::Terminate => "TERM",
+            Signal::Kill => "KILL",
+        }
+    }
+
+    pub fn number(&self) -> i32 {
+        match self {
+            Signal::Interrupt => 2,
+            Signal::Terminate => 15,
+            Signal::Kill => 9,
+        }
+    }
+}
+
+/// When to send a desktop notification for a finished task
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(try_from = "String", into = "String")]
+pub enum NotifyPolicy {
+    Always,
+    #[default]
+    Never,
+    /// Only when the task ran at least this long
+    After(Duration),
+}
+
+impl NotifyPolicy {
+    pub fn should_notify(&self, elapsed: Duration) -> bool {
+        match self {
+            NotifyPolicy::Always => true,
+            NotifyPolicy::Never => false,
+            NotifyPolicy::After(threshold) => elapsed >= *threshold,
+        }
+    }
+}
+
+impl TryFrom<String> for NotifyPolicy {
+    type Error = DomainError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "always" => Ok(NotifyPolicy::Always),
+            "never" => Ok(NotifyPolicy::Never),
+            threshold => parse_duration(threshold).map(NotifyPolicy::After),
+        }
+    }
+}
+
+impl From<NotifyPolicy> for String {
+    fn from(policy: NotifyPolicy) -> Self {
+        match policy {
+            NotifyPolicy::Always => "always".to_string(),
+            NotifyPolicy::Never => "never".to_string(),
+            NotifyPolicy::After(threshold) => duration_setting(threshold),
+        }
+    }
+}
+
+/// A duration setting such as `timeout` or `retry_delay`, e.g. `10m`
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Interval(pub Duration);
+
+impl TryFrom<String> for Interval {
+    type Error = DomainError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        parse_duration(&value).map(Interval)
+    }
+}
+
+impl From<Interval> for String {
+    fn from(interval: Interval) -> Self {
+        duration_setting(interval.0)
+    }
+}
+
+/// A duration the way `parse_duration` reads it back
+fn duration_setting(duration: Duration) -> String {
+    if duration.subsec_millis() > 0 {
+        format!("{}ms", duration.as_millis())
+    } else {
+        format!("{}s", duration.as_secs())
+    }
+}
+
+/// Parse durations such as `500ms`, `2s`, `10m` or `1h`
+pub fn parse_duration(value: &str) -> Result<Duration, DomainError> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+
+    let invalid = || {
+        DomainError::Configuration(format!(
+            "Invalid duration '{}', expected a number followed by ms, s, m or h",
+            value
+        ))
+    };
+    let amount = amount.parse::<u64>().map_err(|_| invalid())?;
+
+    let seconds = |factor: u64| {
+        amount
+            .checked_mul(factor)
+            .map(Duration::from_secs)
+            .ok_or_else(|| DomainError::Configuration(format!("Duration '{}' is too long", value)))
+    };
+    match unit.trim() {
+        "ms" => Ok(Duration::from_millis(amount)),
+        "s" | "" => seconds(1),
+        "m" => seconds(60),
+        "h" => seconds(3600),
+        _ => Err(invalid()),
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Task {
+    pub name: String,
     pub command: Command,
     pub settings: TaskSettings,
+    /// Arguments given after `--`, kept to run the task again
+    pub extra_args: Vec<String>,
+    /// Values of the task's params, kept to run the task again
+    pub params: BTreeMap<String, String>,
 }
 
 impl Task {
-    pub fn new(command: Command, settings: TaskSettings) -> Self {
-        Self { command, settings }
+    pub fn new(name: &str, command: Command, settings: TaskSettings) -> Self {
+        Self {
+            name: name.to_string(),
+            command,
+            settings,
+            extra_args: vec![],
+            params: BTreeMap::new(),
+        }
+    }
+}
+
+/// Lifecycle state of the latest run of a task
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    Running,
+    Success,
+    Failed,
+    #[serde(rename = "timed-out")]
+    TimedOut,
+}
+
+impl Display for RunStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            RunStatus::Running => write!(f, "running"),
+            RunStatus::Success => write!(f, "success"),
+            RunStatus::Failed => write!(f, "failed"),
+            RunStatus::TimedOut => write!(f, "timed-out"),
+        }
+    }
+}
+
+/// Latest known state of a task, persisted for `wzb statusline`
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct TaskRun {
+    pub name: String,
+    pub status: RunStatus,
+    pub pane_id: String,
+    /// Process id of the `wzb` invocation driving the task
+    pub pid: u32,
+    /// Seconds since the Unix epoch
+    pub started_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+}
+
+/// A finished task run, appended to the project's history for `wzb history` and `wzb again`
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub name: String,
+    /// Command line once variables, params and extra arguments were filled in
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// Seconds since the Unix epoch
+    pub started_at: u64,
+    pub duration_ms: u64,
+    /// Missing when the task was killed by a signal or could not run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// Stopped because it outlived its timeout
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub timed_out: bool,
+    /// Which run of a task with retries this was, counting from 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attempt: Option<u32>,
+    /// The timeout the run had, from the config or `--timeout`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<Interval>,
+    pub pane_id: String,
+    pub close: TaskClose,
+    pub direction: Direction,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_args: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, String>,
+}
+
+impl HistoryEntry {
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == Some(0) && !self.timed_out
+    }
+}
+
+/// How one task of an invocation running several ended
+#[derive(Debug)]
+pub struct TaskOutcome {
+    pub name: String,
+    /// `None` when the task never started because an earlier one failed
+    pub result: Option<anyhow::Result<ExitStatus>>,
+    pub elapsed: Duration,
+    pub timed_out: bool,
+    /// Runs it took, more than one when the task was retried
+    pub attempts: u32,
+}
+
+impl TaskOutcome {
+    pub fn succeeded(&self) -> bool {
+        matches!(&self.result, Some(Ok(status)) if status.success()) && !self.timed_out
+    }
+}
+
+/// Compact human readable duration, e.g. `850ms`, `12s`, `3m 05s`, `1h 02m`
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0 => format!("{}ms", duration.as_millis()),
+        1..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m {:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {:02}m", seconds / 3600, (seconds % 3600) / 60),
+    }
+}
+
+/// Tasks by name, ordered so every listing is stable
+pub type TaskConfig = BTreeMap<String, Command>;
+
+/// Project wide settings read from the `[settings]` table
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct Settings {
+    pub backend: Option<BackendKind>,
+
+    /// Default notification policy for every task
+    #[serde(default)]
+    pub notify: NotifyPolicy,
+
+    /// Default interpreter of `script` tasks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+
+    /// Hooks of every task, run around the task's own
+    #[serde(flatten)]
+    pub hooks: Hooks,
+}
+
+/// Parsed project configuration: global settings plus every task table
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct ProjectConfig {
+    #[serde(default)]
+    pub settings: Settings,
+
+    /// Values referenced as `${name}` from task definitions
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vars: BTreeMap<String, String>,
+
+    /// Tasks by name, `namespace:task` for tables nested in a namespace
+    #[serde(flatten, deserialize_with = "namespaced_tasks")]
+    pub tasks: TaskConfig,
+
+    /// Directory holding `.wez`, filled in when the config is loaded
+    #[serde(skip)]
+    pub root: String,
+}
+
+/// Separates a namespace from the tasks in it, as in `rust:build`
+pub const NAMESPACE_SEPARATOR: char = ':';
+
+/// Keys of a `Command` table, which tell a task from a namespace
+const COMMAND_FIELDS: [&str; 25] = [
+    "program",
+    "args",
+    "extends",
+    "append_args",
+    "steps",
+    "script",
+    "shell",
+    "notify",
+    "timeout",
+    "retries",
+    "retry_delay",
+    "retry_on",
+    "before",
+    "after",
+    "on_success",
+    "on_failure",
+    "aliases",
+    "key",
+    "description",
+    "icon",
+    "cwd",
+    "env",
+    "params",
+    "group",
+    "hidden",
+];
+
+fn namespaced_tasks<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<TaskConfig, D::Error> {
+    let tables = toml::Table::deserialize(deserializer)?;
+    let mut tasks = TaskConfig::new();
+    add_tasks("", tables, &mut tasks).map_err(serde::de::Error::custom)?;
+    Ok(tasks)
+}
+
+fn add_tasks(prefix: &str, tables: toml::Table, tasks: &mut TaskConfig) -> Result<(), String> {
+    for (name, value) in tables {
+        let name = format!("{}{}", prefix, name);
+        match value {
+            toml::Value::Table(table) if is_namespace(&table) => {
+                add_tasks(&format!("{}{}", name, NAMESPACE_SEPARATOR), table, tasks)?;
+            }
+            toml::Value::Table(table) => {
+                check_not_mixed(&name, &table)?;
+                let command = toml::Value::Table(table)
+                    .try_into::<Command>()
+                    .map_err(|err| format!("task '{}': {}", name, err.message()))?;
+                tasks.insert(name, command);
+            }
+            value => {
+                let command = value
+                    .try_into::<Command>()
+                    .map_err(|err| format!("task '{}': {}", name, err.message()))?;
+                tasks.insert(name, command);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A table is a namespace when none of its keys is a task field
+fn is_namespace(table: &toml::Table) -> bool {
+    !table.is_empty()
+        && !table
+            .keys()
+            .any(|key| COMMAND_FIELDS.contains(&key.as_str()))
+}
+
+/// A task can't hold nested tasks, which would be lost
+fn check_not_mixed(name: &str, table: &toml::Table) -> Result<(), String> {
+    let nested = table
+        .iter()
+        .filter(|(key, value)| value.is_table() && !COMMAND_FIELDS.contains(&key.as_str()))
+        .map(|(key, _)| key.as_str())
+        .collect::<Vec<_>>();
+    if nested.is_empty() {
+        return Ok(());
+    }
+    let fields = table
+        .keys()
+        .filter(|key| COMMAND_FIELDS.contains(&key.as_str()))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    Err(format!(
+        "table '{}' mixes task fields ({}) with nested tables ({}), move the task into \
+         the namespace or rename nested tasks named like a task field",
+        name,
+        fields.join(", "),
+        nested.join(", ")
+    ))
+}
+
+/// Output formats of `wzb config view`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    /// One `[task] program args` line per task
+    Text,
+    /// Aligned columns for people
+    Table,
+    /// Every task field plus the source file, for scripts and editors
+    Json,
+    /// The parsed configuration written back as TOML
+    Toml,
+}
+
+/// Terminal backends able to host task panes
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    WezTerm,
+    Tmux,
+    Local,
+}
+
+impl Display for BackendKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            BackendKind::WezTerm => write!(f, "wezterm"),
+            BackendKind::Tmux => write!(f, "tmux"),
+            BackendKind::Local => write!(f, "local"),
+        }
     }
 }
 
-pub type TaskConfig = HashMap<String, Command>;
+impl FromStr for BackendKind {
+    type Err = DomainError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "wezterm" => Ok(BackendKind::WezTerm),
+            "tmux" => Ok(BackendKind::Tmux),
+            "local" => Ok(BackendKind::Local),
+            other => Err(DomainError::Configuration(format!(
+                "Unknown backend '{}', expected one of: wezterm, tmux, local",
+                other
+            ))),
+        }
+    }
+}
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
 pub enum Direction {
     Right,
     Down,
@@ -130,9 +1098,340 @@ impl Display for Direction {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
 pub enum TaskClose {
     Always,
     OnSuccess,
     Never,
 }
+
+impl Display for TaskClose {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            TaskClose::Always => write!(f, "always"),
+            TaskClose::OnSuccess => write!(f, "on-success"),
+            TaskClose::Never => write!(f, "never"),
+        }
+    }
+}
+
+/// A key press read from the controlling terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    /// Control plus a lowercase letter, e.g. `Ctrl('d')`
+    Ctrl(char),
+    Enter,
+    AltEnter,
+    Esc,
+    Backspace,
+    Up,
+    Down,
+}
+
+/// Outcome of a single `wzb doctor` check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl Check {
+    pub fn new(name: &str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            detail: detail.into(),
+        }
+    }
+
+    /// How `wzb doctor` exits when this check fails: backend checks point at
+    /// the terminal, the state directory at the file system, the rest at the config
+    pub fn failure_kind(&self) -> FailureKind {
+        match self.name.as_str() {
+            "wezterm" | "mux" | "pane" | "tmux" => FailureKind::Terminal,
+            "state directory" => FailureKind::FileSystem,
+            _ => FailureKind::Configuration,
+        }
+    }
+}
+
+impl Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let marker = match self.status {
+            CheckStatus::Ok => "ok",
+            CheckStatus::Warning => "warn",
+            CheckStatus::Error => "fail",
+        };
+        write!(f, "[{:>4}] {}: {}", marker, self.name, self.detail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("2s").unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_duration("10m").unwrap(), Duration::from_secs(600));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(3600));
+        assert!(parse_duration("soon").is_err());
+        assert!(parse_duration("10 days").is_err());
+        assert!(matches!(
+            parse_duration(&format!("{}h", u64::MAX / 60)),
+            Err(DomainError::Configuration(_))
+        ));
+        assert!(parse_duration(&format!("{}m", u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_notify_policy() {
+        let policy = NotifyPolicy::try_from("30s".to_string()).unwrap();
+        assert!(!policy.should_notify(Duration::from_secs(29)));
+        assert!(policy.should_notify(Duration::from_secs(30)));
+        assert!(NotifyPolicy::Always.should_notify(Duration::ZERO));
+        assert!(!NotifyPolicy::Never.should_notify(Duration::from_secs(3600)));
+        assert!(NotifyPolicy::try_from("sometimes".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_task_timeout() {
+        let command: Command = toml::from_str("program = \"make\"\ntimeout = \"10m\"\n").unwrap();
+        assert_eq!(command.timeout, Some(Interval(Duration::from_secs(600))));
+        assert_eq!(
+            String::from(Interval(Duration::from_millis(1500))),
+            "1500ms"
+        );
+        assert!(toml::from_str::<Command>("timeout = \"forever\"\n").is_err());
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let any = RetryPolicy {
+            retries: 2,
+            ..Default::default()
+        };
+        let exit = |code: i32| ExitStatus::from_raw(code << 8);
+        let killed = |signal: i32| ExitStatus::from_raw(signal);
+        assert_eq!(any.attempts(), 3);
+        assert!(any.should_retry(1, &exit(1), false));
+        assert!(any.should_retry(2, &killed(9), false));
+        assert!(!any.should_retry(3, &exit(1), false));
+
+        // Stopped from the pane rather than failed, unless by the timeout
+        assert!(!any.should_retry(1, &exit(130), false));
+        assert!(!any.should_retry(1, &exit(143), false));
+        assert!(!any.should_retry(1, &killed(2), false));
+        assert!(!any.should_retry(1, &killed(15), false));
+        assert!(any.should_retry(1, &exit(130), true));
+
+        let ports = RetryPolicy {
+            retries: 2,
+            on: vec![98, 130],
+            ..Default::default()
+        };
+        assert!(ports.should_retry(1, &exit(98), false));
+        assert!(ports.should_retry(1, &exit(130), false));
+        assert!(!ports.should_retry(1, &exit(1), false));
+        assert!(!ports.should_retry(1, &killed(9), false));
+        assert!(!RetryPolicy::default().should_retry(1, &exit(98), false));
+    }
+
+    #[test]
+    fn test_hooks_in_settings_and_tasks() {
+        let config: ProjectConfig = toml::from_str(
+            "[settings]\non_failure = \"paplay fail.oga\"\n\n[test]\nprogram = \"cargo\"\nbefore = \"docker compose up -d db\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.settings.hooks.get(HookKind::OnFailure),
+            Some("paplay fail.oga")
+        );
+        let test = &config.tasks["test"];
+        assert_eq!(test.program, "cargo");
+        assert_eq!(
+            test.hooks.get(HookKind::Before),
+            Some("docker compose up -d db")
+        );
+        assert_eq!(test.hooks.get(HookKind::After), None);
+
+        let toml = toml::to_string(test).unwrap();
+        assert!(
+            toml.contains("before = \"docker compose up -d db\""),
+            "{}",
+            toml
+        );
+        assert!(!toml.contains("after"), "{}", toml);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_millis(850)), "850ms");
+        assert_eq!(format_duration(Duration::from_secs(12)), "12s");
+        assert_eq!(format_duration(Duration::from_secs(185)), "3m 05s");
+        assert_eq!(format_duration(Duration::from_secs(3720)), "1h 02m");
+    }
+
+    #[test]
+    fn test_failure_kind_exit_codes_are_distinct() {
+        let kinds = [
+            FailureKind::Usage,
+            FailureKind::TaskNotConfigured,
+            FailureKind::Terminal,
+            FailureKind::Internal,
+            FailureKind::FileSystem,
+            FailureKind::Configuration,
+            FailureKind::TimedOut,
+        ];
+        let mut codes = kinds.map(FailureKind::exit_code).to_vec();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), kinds.len());
+    }
+
+    #[test]
+    fn test_failure_kind_from_context() {
+        let err = Err::<(), _>(std::io::Error::other("denied"))
+            .with_context(|| FileSystemError::Read("config.toml".to_string()))
+            .with_context(|| ConfigError::Load("config.toml".to_string()))
+            .context("Failed to run application")
+            .unwrap_err();
+        assert_eq!(FailureKind::from_error(&err), FailureKind::Configuration);
+
+        let err = anyhow::anyhow!(TerminalError::OpenPane("right".to_string()));
+        assert_eq!(FailureKind::from_error(&err), FailureKind::Terminal);
+
+        let err = anyhow::anyhow!("unexpected");
+        assert_eq!(FailureKind::from_error(&err), FailureKind::Internal);
+    }
+
+    #[test]
+    fn test_params_read_scalars_as_text() {
+        let command: Command = toml::from_str(
+            r#"
+            program = "./migrate.sh"
+            args = ["{steps}"]
+            params = [
+                { name = "steps", type = "int", choices = [1, 5], default = 1 },
+                { name = "dry_run", type = "bool", default = false },
+                { name = "note" },
+            ]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(command.params[0].kind, ParamType::Int);
+        assert_eq!(command.params[0].choices, ["1", "5"]);
+        assert_eq!(command.params[0].default.as_deref(), Some("1"));
+        assert_eq!(command.params[1].default.as_deref(), Some("false"));
+        assert_eq!(command.params[2].kind, ParamType::String);
+        assert_eq!(command.params[2].default, None);
+    }
+
+    #[test]
+    fn test_nested_tables_are_namespaces() {
+        let config: ProjectConfig = toml::from_str(
+            r#"
+            [settings]
+            notify = "always"
+
+            [lint]
+            program = "make"
+            env = { CI = "1" }
+
+            [rust.build]
+            program = "cargo"
+            args = ["build"]
+
+            [rust.wasm.build]
+            program = "wasm-pack"
+
+            [web.build]
+            program = "npm"
+            env = { NODE_ENV = "production" }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.tasks.keys().collect::<Vec<_>>(),
+            ["lint", "rust:build", "rust:wasm:build", "web:build"]
+        );
+        assert_eq!(config.tasks["lint"].env["CI"], "1");
+        assert_eq!(config.tasks["web:build"].env["NODE_ENV"], "production");
+        assert_eq!(config.settings.notify, NotifyPolicy::Always);
+
+        let err = toml::from_str::<ProjectConfig>("[rust.build]\nprogram = 1\n").unwrap_err();
+        assert!(err.to_string().contains("task 'rust:build'"), "{}", err);
+
+        // A task can't also hold tasks, whether the clash is a field or a task
+        let err = toml::from_str::<ProjectConfig>(
+            "[rust]\nprogram = \"cargo\"\n[rust.build]\nprogram = \"cargo\"\n",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("table 'rust' mixes"), "{}", err);
+        let err = toml::from_str::<ProjectConfig>(
+            "[docker.env]\nprogram = \"x\"\n[docker.up]\nprogram = \"y\"\n",
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("task fields (env) with nested tables (up)"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_command_fields_are_the_task_keys() {
+        // No `..Default::default()`, a new field has to be listed here and in COMMAND_FIELDS
+        let command = Command {
+            program: "cargo".to_string(),
+            args: vec!["test".to_string()],
+            extends: Some("base".to_string()),
+            append_args: vec!["--".to_string()],
+            steps: vec![Step::default()],
+            script: Some("true".to_string()),
+            shell: Some("bash".to_string()),
+            notify: Some(NotifyPolicy::Always),
+            timeout: Some(Interval(Duration::from_secs(1))),
+            retries: Some(1),
+            retry_delay: Some(Interval(Duration::from_secs(1))),
+            retry_on: vec![1],
+            hooks: Hooks {
+                before: Some("true".to_string()),
+                after: Some("true".to_string()),
+                on_success: Some("true".to_string()),
+                on_failure: Some("true".to_string()),
+            },
+            aliases: vec!["t".to_string()],
+            key: Some("ctrl-t".to_string()),
+            description: Some("Test".to_string()),
+            icon: Some("md_test_tube".to_string()),
+            cwd: Some("backend".to_string()),
+            env: BTreeMap::from([("CI".to_string(), "1".to_string())]),
+            params: vec![Param::default()],
+            group: Some("checks".to_string()),
+            hidden: true,
+        };
+
+        let table = toml::Table::try_from(command).unwrap();
+        let mut keys = table.keys().map(String::as_str).collect::<Vec<_>>();
+        let mut fields = COMMAND_FIELDS.to_vec();
+        keys.sort();
+        fields.sort();
+        assert_eq!(keys, fields);
+    }
+}
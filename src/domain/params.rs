@@ -0,0 +1,402 @@
+//! Typed task parameters: `--param` values, prompts for the missing ones and
+//! `{name}` substitution
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+
+use crate::domain::models::{DomainError, Key, Param, ParamType};
+use crate::ports::PromptPort;
+
+/// Values for every parameter of `task_name`, `None` when the prompt was cancelled.
+/// Parameters missing from `given` are asked for on an interactive terminal,
+/// and fall back to their default otherwise.
+pub fn resolve_params(
+    task_name: &str,
+    params: &[Param],
+    given: &[(String, String)],
+    prompt: &impl PromptPort,
+) -> Result<Option<BTreeMap<String, String>>> {
+    check_declarations(task_name, params)?;
+
+    let mut values = BTreeMap::new();
+    for (name, value) in given {
+        let param = params
+            .iter()
+            .find(|param| &param.name == name)
+            .ok_or_else(|| DomainError::UnknownParam(task_name.to_string(), name.clone()))?;
+        check_value(param, value).map_err(|reason| {
+            DomainError::InvalidParam(task_name.to_string(), name.clone(), reason)
+        })?;
+        values.insert(name.clone(), value.clone());
+    }
+
+    let missing = params
+        .iter()
+        .filter(|param| !values.contains_key(&param.name))
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        return Ok(Some(values));
+    }
+
+    if !prompt.is_interactive() {
+        for param in missing {
+            let value = param.default.clone().ok_or_else(|| {
+                DomainError::MissingParam(task_name.to_string(), param.name.clone())
+            })?;
+            values.insert(param.name.clone(), value);
+        }
+        return Ok(Some(values));
+    }
+
+    prompt.start()?;
+    let asked = ask_all(task_name, &missing, prompt);
+    // Always restore the terminal, even when reading keys failed
+    let finished = prompt.finish();
+    let asked = asked?;
+    finished?;
+
+    Ok(asked.map(|asked| {
+        values.extend(asked);
+        values
+    }))
+}
+
+fn ask_all(
+    task_name: &str,
+    params: &[&Param],
+    prompt: &impl PromptPort,
+) -> Result<Option<BTreeMap<String, String>>> {
+    let mut values = BTreeMap::new();
+    for param in params {
+        match ParamPrompt::new(task_name, param).ask(prompt)? {
+            Some(value) => values.insert(param.name.clone(), value),
+            None => return Ok(None),
+        };
+    }
+    Ok(Some(values))
+}
+
+/// Substitute `{name}` for each parameter value in `text`, in one pass so
+/// braces in the values are kept as they are
+pub fn substitute(text: &str, values: &BTreeMap<String, String>) -> String {
+    let mut substituted = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let value = rest[start..]
+            .find('}')
+            .and_then(|end| Some((end, values.get(&rest[start + 1..start + end])?)));
+        match value {
+            Some((end, value)) => {
+                substituted.push_str(&rest[..start]);
+                substituted.push_str(value);
+                rest = &rest[start + end + 1..];
+            }
+            None => {
+                substituted.push_str(&rest[..=start]);
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    substituted.push_str(rest);
+    substituted
+}
+
+/// Names must not clash with the `{args}` and `{1}` placeholders of extra arguments
+fn check_declarations(task_name: &str, params: &[Param]) -> Result<(), DomainError> {
+    for (index, param) in params.iter().enumerate() {
+        let invalid = |reason: String| {
+            DomainError::Configuration(format!(
+                "Parameter '{}' of task '{}' {}",
+                param.name, task_name, reason
+            ))
+        };
+        if param.name.is_empty()
+            || param.name == "args"
+            || param.name.chars().all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid(
+                "needs a name other than `args` or a number".to_string(),
+            ));
+        }
+        if params[..index].iter().any(|other| other.name == param.name) {
+            return Err(invalid("is declared twice".to_string()));
+        }
+        let typed = Param {
+            choices: vec![],
+            ..param.clone()
+        };
+        for choice in &param.choices {
+            check_value(&typed, choice)
+                .map_err(|reason| invalid(format!("has an invalid choice: {}", reason)))?;
+        }
+        if let Some(default) = &param.default {
+            check_value(param, default)
+                .map_err(|reason| invalid(format!("has an invalid default: {}", reason)))?;
+        }
+    }
+    Ok(())
+}
+
+/// Why `value` isn't acceptable for `param`
+pub fn check_value(param: &Param, value: &str) -> Result<(), String> {
+    let parses = match param.kind {
+        ParamType::String => true,
+        ParamType::Int => value.parse::<i64>().is_ok(),
+        ParamType::Float => value.parse::<f64>().is_ok(),
+        ParamType::Bool => matches!(value, "true" | "false"),
+    };
+    if !parses {
+        return Err(format!("'{}' is not a valid {}", value, param.kind));
+    }
+    if !param.choices.is_empty() && !param.choices.iter().any(|choice| choice == value) {
+        return Err(format!(
+            "'{}' is not one of {}",
+            value,
+            param.choices.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// What a key press did to the prompt
+#[derive(Debug, PartialEq)]
+pub enum PromptEvent {
+    Continue,
+    Done(String),
+    Cancel,
+}
+
+/// Asks for one parameter value: typed in, or selected when there are choices
+pub struct ParamPrompt<'a> {
+    task_name: &'a str,
+    param: &'a Param,
+    input: String,
+    selected: usize,
+    error: Option<String>,
+}
+
+impl<'a> ParamPrompt<'a> {
+    pub fn new(task_name: &'a str, param: &'a Param) -> Self {
+        let default = param.default.clone().unwrap_or_default();
+        Self {
+            task_name,
+            param,
+            selected: param
+                .choices
+                .iter()
+                .position(|choice| *choice == default)
+                .unwrap_or(0),
+            input: default,
+            error: None,
+        }
+    }
+
+    /// Read keys until a valid value is entered, `None` when cancelled
+    pub fn ask(mut self, prompt: &impl PromptPort) -> Result<Option<String>> {
+        loop {
+            prompt.draw(&self.render())?;
+            match self.handle(prompt.read_key()?) {
+                PromptEvent::Continue => {}
+                PromptEvent::Done(value) => return Ok(Some(value)),
+                PromptEvent::Cancel => return Ok(None),
+            }
+        }
+    }
+
+    pub fn handle(&mut self, key: Key) -> PromptEvent {
+        let choices = &self.param.choices;
+        match key {
+            Key::Enter | Key::AltEnter => {
+                let value = match choices.get(self.selected) {
+                    Some(choice) => choice.clone(),
+                    None => self.input.clone(),
+                };
+                match check_value(self.param, &value) {
+                    Ok(()) => return PromptEvent::Done(value),
+                    Err(reason) => self.error = Some(reason),
+                }
+            }
+            Key::Esc | Key::Ctrl('c') => return PromptEvent::Cancel,
+            Key::Up | Key::Ctrl('p') if !choices.is_empty() => {
+                self.selected = (self.selected + choices.len() - 1) % choices.len();
+            }
+            Key::Down | Key::Ctrl('n') if !choices.is_empty() => {
+                self.selected = (self.selected + 1) % choices.len();
+            }
+            Key::Char(c) if choices.is_empty() => {
+                self.input.push(c);
+                self.error = None;
+            }
+            Key::Backspace => {
+                self.input.pop();
+                self.error = None;
+            }
+            Key::Ctrl('u') => {
+                self.input.clear();
+                self.error = None;
+            }
+            _ => {}
+        }
+        PromptEvent::Continue
+    }
+
+    pub fn render(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "{}: {} ({})",
+            self.task_name, self.param.name, self.param.kind
+        )];
+        if self.param.choices.is_empty() {
+            lines.push(format!("> {}█", self.input));
+        } else {
+            for (index, choice) in self.param.choices.iter().enumerate() {
+                let marker = if index == self.selected { ">" } else { " " };
+                lines.push(format!("{} {}", marker, choice));
+            }
+        }
+        if let Some(error) = &self.error {
+            lines.push(String::new());
+            lines.push(format!("✘ {}", error));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::FailureKind;
+    use crate::ports::mock::MockPromptPort;
+
+    fn params() -> Vec<Param> {
+        vec![
+            Param {
+                name: "env".to_string(),
+                choices: vec!["dev".to_string(), "staging".to_string()],
+                default: Some("dev".to_string()),
+                ..Default::default()
+            },
+            Param {
+                name: "count".to_string(),
+                kind: ParamType::Int,
+                ..Default::default()
+            },
+        ]
+    }
+
+    fn script_prompt() -> MockPromptPort {
+        let mut prompt = MockPromptPort::new();
+        prompt.expect_is_interactive().returning(|| false);
+        prompt
+    }
+
+    fn given(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_given_values_and_defaults() {
+        let values = resolve_params(
+            "deploy",
+            &params(),
+            &given(&[("count", "3")]),
+            &script_prompt(),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(values["env"], "dev");
+        assert_eq!(values["count"], "3");
+    }
+
+    #[test]
+    fn test_invalid_unknown_and_missing_values_are_usage_errors() {
+        for (pairs, message) in [
+            (
+                vec![("count", "3"), ("env", "prod")],
+                "'prod' is not one of dev, staging",
+            ),
+            (vec![("count", "three")], "'three' is not a valid int"),
+            (
+                vec![("count", "3"), ("region", "eu")],
+                "Task 'deploy' has no parameter 'region'",
+            ),
+            (vec![], "needs parameter 'count'"),
+        ] {
+            let err =
+                resolve_params("deploy", &params(), &given(&pairs), &script_prompt()).unwrap_err();
+            assert!(err.to_string().contains(message), "{}", err);
+            assert_eq!(FailureKind::from_error(&err), FailureKind::Usage);
+        }
+    }
+
+    #[test]
+    fn test_invalid_declarations_are_config_errors() {
+        let mut params = params();
+        params[0].default = Some("prod".to_string());
+        let err = resolve_params("deploy", &params, &[], &script_prompt()).unwrap_err();
+        assert_eq!(FailureKind::from_error(&err), FailureKind::Configuration);
+
+        let params = vec![Param {
+            name: "args".to_string(),
+            ..Default::default()
+        }];
+        let err = resolve_params("deploy", &params, &[], &script_prompt()).unwrap_err();
+        assert_eq!(FailureKind::from_error(&err), FailureKind::Configuration);
+    }
+
+    #[test]
+    fn test_prompts_for_missing_values() {
+        let mut prompt = MockPromptPort::new();
+        prompt.expect_is_interactive().returning(|| true);
+        prompt.expect_start().times(1).returning(|| Ok(()));
+        prompt.expect_finish().times(1).returning(|| Ok(()));
+        prompt.expect_draw().returning(|_| Ok(()));
+        // Pick `staging`, then type `1x`, get told off, fix it to `12`
+        let mut keys = vec![
+            Key::Down,
+            Key::Enter,
+            Key::Char('1'),
+            Key::Char('x'),
+            Key::Enter,
+            Key::Backspace,
+            Key::Char('2'),
+            Key::Enter,
+        ]
+        .into_iter();
+        prompt
+            .expect_read_key()
+            .returning(move || Ok(keys.next().unwrap()));
+
+        let values = resolve_params("deploy", &params(), &[], &prompt)
+            .unwrap()
+            .unwrap();
+        assert_eq!(values["env"], "staging");
+        assert_eq!(values["count"], "12");
+    }
+
+    #[test]
+    fn test_prompt_shows_errors_and_cancels() {
+        let params = params();
+        let mut ask = ParamPrompt::new("deploy", &params[1]);
+        ask.handle(Key::Char('x'));
+        assert_eq!(ask.handle(Key::Enter), PromptEvent::Continue);
+        assert_eq!(ask.render().last().unwrap(), "✘ 'x' is not a valid int");
+        assert_eq!(ask.handle(Key::Esc), PromptEvent::Cancel);
+    }
+
+    #[test]
+    fn test_substitute_places_values_once() {
+        let values = BTreeMap::from([
+            ("env".to_string(), "staging".to_string()),
+            ("count".to_string(), "2".to_string()),
+            ("name".to_string(), "{env}-{1}".to_string()),
+        ]);
+        assert_eq!(substitute("--env={env}", &values), "--env=staging");
+        assert_eq!(substitute("{count}{}{other}", &values), "2{}{other}");
+        assert_eq!(substitute("{{env}}", &values), "{staging}");
+        assert_eq!(substitute("{name}", &values), "{env}-{1}");
+    }
+}
@@ -0,0 +1,468 @@
+//! Built-in fuzzy finder used by `wzb task-runner` when no task is named
+
+use anyhow::Result;
+
+use super::models::{
+    format_duration, Direction, Key, ProjectConfig, RunStatus, TaskClose, TaskRun,
+};
+use crate::ports::PromptPort;
+use std::time::Duration;
+
+/// Lines of task details below the list
+const PREVIEW_ROWS: usize = 4;
+
+/// Query, status, separator and help lines around the list and preview
+const CHROME_ROWS: usize = PREVIEW_ROWS + 4;
+
+const HELP: &str = "enter run · alt-enter keep open · ^D split · ^T close · esc cancel";
+
+/// A task offered by the picker with its preview
+#[derive(Debug, Clone)]
+pub struct PickerItem {
+    pub name: String,
+    pub preview: Vec<String>,
+}
+
+/// The picked task and how to run it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection {
+    pub name: String,
+    pub close: TaskClose,
+    pub direction: Direction,
+}
+
+/// What the picker needs after a key press
+#[derive(Debug, Clone, PartialEq)]
+pub enum PickerEvent {
+    Continue,
+    Run(Selection),
+    Cancel,
+}
+
+/// Interactive task list filtered by a fuzzy query
+pub struct Picker {
+    items: Vec<PickerItem>,
+    query: String,
+    selected: usize,
+    close: TaskClose,
+    direction: Direction,
+}
+
+impl Picker {
+    /// `close` and `direction` start from the command line options
+    pub fn new(items: Vec<PickerItem>, close: TaskClose, direction: Direction) -> Self {
+        Self {
+            items,
+            query: String::new(),
+            selected: 0,
+            close,
+            direction,
+        }
+    }
+
+    /// Let the user pick a task, `None` when they cancel
+    pub fn pick(mut self, prompt: &impl PromptPort) -> Result<Option<Selection>> {
+        prompt.start()?;
+        let picked = self.read_selection(prompt);
+        // Always restore the terminal, even when reading keys failed
+        let finished = prompt.finish();
+        let picked = picked?;
+        finished?;
+        Ok(picked)
+    }
+
+    fn read_selection(&mut self, prompt: &impl PromptPort) -> Result<Option<Selection>> {
+        loop {
+            let (rows, columns) = prompt.size();
+            prompt.draw(&self.render(rows, columns))?;
+            match self.handle(prompt.read_key()?) {
+                PickerEvent::Continue => {}
+                PickerEvent::Run(selection) => return Ok(Some(selection)),
+                PickerEvent::Cancel => return Ok(None),
+            }
+        }
+    }
+
+    /// Items matching the query, best match first
+    pub fn matches(&self) -> Vec<&PickerItem> {
+        let mut scored = self
+            .items
+            .iter()
+            .filter_map(|item| fuzzy_score(&self.query, &item.name).map(|score| (score, item)))
+            .collect::<Vec<_>>();
+        scored.sort_by(|(score_a, a), (score_b, b)| {
+            score_b.cmp(score_a).then_with(|| a.name.cmp(&b.name))
+        });
+        scored.into_iter().map(|(_, item)| item).collect()
+    }
+
+    pub fn handle(&mut self, key: Key) -> PickerEvent {
+        match key {
+            Key::Char(c) => {
+                self.query.push(c);
+                self.selected = 0;
+            }
+            Key::Backspace => {
+                self.query.pop();
+                self.selected = 0;
+            }
+            Key::Ctrl('u') => {
+                self.query.clear();
+                self.selected = 0;
+            }
+            Key::Up | Key::Ctrl('p') => self.selected = self.selected.saturating_sub(1),
+            Key::Down | Key::Ctrl('n') => {
+                let last = self.matches().len().saturating_sub(1);
+                self.selected = (self.selected + 1).min(last);
+            }
+            Key::Ctrl('d') => {
+                self.direction = match self.direction {
+                    Direction::Right => Direction::Down,
+                    Direction::Down => Direction::Right,
+                }
+            }
+            Key::Ctrl('t') => {
+                self.close = match self.close {
+                    TaskClose::OnSuccess => TaskClose::Always,
+                    TaskClose::Always => TaskClose::Never,
+                    TaskClose::Never => TaskClose::OnSuccess,
+                }
+            }
+            Key::Enter => return self.run(self.close),
+            Key::AltEnter => return self.run(TaskClose::Never),
+            Key::Esc | Key::Ctrl('c') => return PickerEvent::Cancel,
+            _ => {}
+        }
+        PickerEvent::Continue
+    }
+
+    fn run(&self, close: TaskClose) -> PickerEvent {
+        match self.matches().get(self.selected) {
+            Some(item) => PickerEvent::Run(Selection {
+                name: item.name.clone(),
+                close,
+                direction: self.direction,
+            }),
+            None => PickerEvent::Continue,
+        }
+    }
+
+    /// Screen lines for a terminal of `rows` by `columns`
+    pub fn render(&self, rows: usize, columns: usize) -> Vec<String> {
+        let matches = self.matches();
+        let list_rows = rows.saturating_sub(CHROME_ROWS).max(1);
+        let first = (self.selected + 1).saturating_sub(list_rows);
+
+        let mut lines = vec![
+            format!("> {}", self.query),
+            format!(
+                "  {}/{}  split {} · close {}",
+                matches.len(),
+                self.items.len(),
+                self.direction,
+                self.close
+            ),
+        ];
+
+        let mut list = matches
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(list_rows)
+            .map(|(index, item)| {
+                let marker = if index == self.selected { "▸" } else { " " };
+                format!("{} {}", marker, item.name)
+            })
+            .collect::<Vec<_>>();
+        list.resize(list_rows, String::new());
+        lines.extend(list);
+
+        lines.push("─".repeat(columns));
+        let mut preview = matches
+            .get(self.selected)
+            .map(|item| item.preview.clone())
+            .unwrap_or_default();
+        preview.resize(PREVIEW_ROWS, String::new());
+        lines.extend(preview);
+        lines.push(HELP.to_string());
+
+        lines
+            .into_iter()
+            .map(|line| line.chars().take(columns).collect())
+            .collect()
+    }
+}
+
+/// Every visible task with a preview of what it runs and how it last went
+pub fn picker_items(config: &ProjectConfig, runs: &[TaskRun]) -> Vec<PickerItem> {
+    config
+        .tasks
+        .iter()
+        .filter(|(name, command)| !command.is_hidden(name))
+        .map(|(name, command)| {
+            let last_run = runs.iter().find(|run| &run.name == name);
+            let mut preview = if !command.steps.is_empty() {
+                vec![format!("steps     {}", command.command_line())]
+            } else if command.script.is_some() {
+                vec![format!("script    {}", command.command_line())]
+            } else {
+                vec![
+                    format!("program   {}", command.program),
+                    format!("args      {}", command.args.join(" ")),
+                ]
+            };
+            preview.push(format!(
+                "cwd       {}",
+                command.cwd.as_deref().unwrap_or("(pane directory)")
+            ));
+            preview.push(format!("last run  {}", describe_run(last_run)));
+            PickerItem {
+                name: name.clone(),
+                preview,
+            }
+        })
+        .collect()
+}
+
+fn describe_run(run: Option<&TaskRun>) -> String {
+    let Some(run) = run else {
+        return "never".to_string();
+    };
+    let took = |word: &str| {
+        run.duration_ms
+            .map(|ms| format!(" {} {}", word, format_duration(Duration::from_millis(ms))))
+            .unwrap_or_default()
+    };
+
+    match (run.status, run.exit_code) {
+        (RunStatus::Running, _) => "▶ running".to_string(),
+        (RunStatus::Success, _) => format!("✔ success{}", took("in")),
+        (RunStatus::Failed, Some(code)) => format!("✘ exit {}{}", code, took("after")),
+        (RunStatus::Failed, None) => "✘ failed".to_string(),
+        (RunStatus::TimedOut, _) => format!("⏱ timed out{}", took("after")),
+    }
+}
+
+/// Score `candidate` when it contains the query as a subsequence.
+/// Lowercase queries ignore case, any uppercase letter makes the match exact.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let case_sensitive = query.chars().any(char::is_uppercase);
+    let fold = |c: char| {
+        if case_sensitive {
+            c
+        } else {
+            c.to_ascii_lowercase()
+        }
+    };
+    let chars = candidate.chars().collect::<Vec<_>>();
+
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous = None;
+    for wanted in query.chars().map(fold) {
+        let index = (position..chars.len()).find(|&index| fold(chars[index]) == wanted)?;
+
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == index) {
+            score += 5;
+        }
+        if index == 0 || "-_:./ ".contains(chars[index - 1]) {
+            score += 4;
+        }
+        if previous.is_some() && index > position {
+            score -= 1;
+        }
+
+        position = index + 1;
+        previous = Some(index);
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::Command;
+    use crate::ports::mock::MockPromptPort;
+    use std::collections::VecDeque;
+
+    fn items(names: &[&str]) -> Vec<PickerItem> {
+        names
+            .iter()
+            .map(|name| PickerItem {
+                name: name.to_string(),
+                preview: vec![format!("program   {}", name)],
+            })
+            .collect()
+    }
+
+    fn names(picker: &Picker) -> Vec<&str> {
+        picker
+            .matches()
+            .into_iter()
+            .map(|item| item.name.as_str())
+            .collect()
+    }
+
+    fn scripted_prompt(keys: Vec<Key>) -> MockPromptPort {
+        let mut keys = VecDeque::from(keys);
+        let mut prompt = MockPromptPort::new();
+        prompt.expect_start().times(1).returning(|| Ok(()));
+        prompt.expect_finish().times(1).returning(|| Ok(()));
+        prompt.expect_size().returning(|| (20, 80));
+        prompt.expect_draw().returning(|_| Ok(()));
+        prompt
+            .expect_read_key()
+            .returning(move || Ok(keys.pop_front().expect("script ran out of keys")));
+        prompt
+    }
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert!(fuzzy_score("bld", "build").is_some());
+        assert!(fuzzy_score("dlb", "build").is_none());
+        assert!(fuzzy_score("", "build").is_some());
+        // Smart case: lowercase matches any case, uppercase only itself
+        assert!(fuzzy_score("q", "Q").is_some());
+        assert!(fuzzy_score("Q", "q").is_none());
+        // Contiguous and word start matches rank first
+        assert!(fuzzy_score("test", "test-all") > fuzzy_score("test", "the-rest-t"));
+        assert!(fuzzy_score("ta", "test-all") > fuzzy_score("ta", "start"));
+    }
+
+    #[test]
+    fn test_filters_and_ranks_matches() {
+        let mut picker = Picker::new(
+            items(&["build", "lint", "test", "test-all"]),
+            TaskClose::OnSuccess,
+            Direction::Right,
+        );
+        assert_eq!(names(&picker), ["build", "lint", "test", "test-all"]);
+
+        for c in "tst".chars() {
+            picker.handle(Key::Char(c));
+        }
+        assert_eq!(names(&picker), ["test", "test-all"]);
+
+        picker.handle(Key::Ctrl('u'));
+        assert_eq!(names(&picker).len(), 4);
+    }
+
+    #[test]
+    fn test_pick_runs_selection_with_toggles() {
+        let prompt = scripted_prompt(vec![
+            Key::Char('t'),
+            Key::Down,
+            Key::Ctrl('d'),
+            Key::Ctrl('t'),
+            Key::Enter,
+        ]);
+        let picker = Picker::new(
+            items(&["build", "lint", "test", "test-all"]),
+            TaskClose::OnSuccess,
+            Direction::Right,
+        );
+
+        let selection = picker.pick(&prompt).unwrap();
+        assert_eq!(
+            selection,
+            Some(Selection {
+                name: "test-all".to_string(),
+                close: TaskClose::Always,
+                direction: Direction::Down,
+            })
+        );
+    }
+
+    #[test]
+    fn test_pick_alt_enter_keeps_pane_and_esc_cancels() {
+        let picker = || Picker::new(items(&["build"]), TaskClose::OnSuccess, Direction::Right);
+
+        let selection = picker()
+            .pick(&scripted_prompt(vec![Key::AltEnter]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(selection.close, TaskClose::Never);
+
+        // Enter without a match does nothing, Esc gives up
+        let selection = picker()
+            .pick(&scripted_prompt(vec![Key::Char('x'), Key::Enter, Key::Esc]))
+            .unwrap();
+        assert_eq!(selection, None);
+    }
+
+    #[test]
+    fn test_render_fits_terminal() {
+        let mut picker = Picker::new(
+            items(&["a", "b", "c", "d", "e"]),
+            TaskClose::Never,
+            Direction::Down,
+        );
+        for _ in 0..4 {
+            picker.handle(Key::Down);
+        }
+
+        let lines = picker.render(10, 40);
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[1], "  5/5  split down · close never");
+        // Two list rows scrolled down to the selection
+        assert_eq!(lines[2..4], ["  d".to_string(), "▸ e".to_string()]);
+        assert_eq!(lines[5], "program   e");
+        assert!(lines.iter().all(|line| line.chars().count() <= 40));
+    }
+
+    #[test]
+    fn test_picker_items_preview_last_run() {
+        let mut config = ProjectConfig::default();
+        config.tasks.insert(
+            "test".to_string(),
+            Command {
+                program: "cargo".to_string(),
+                args: vec!["test".to_string(), "--all".to_string()],
+                cwd: Some("crates/core".to_string()),
+                ..Default::default()
+            },
+        );
+        config.tasks.insert("build".to_string(), Command::default());
+        config.tasks.insert(
+            "seed".to_string(),
+            Command {
+                script: Some("echo seeded".to_string()),
+                shell: Some("bash".to_string()),
+                args: vec!["users".to_string()],
+                ..Default::default()
+            },
+        );
+        let runs = vec![TaskRun {
+            name: "test".to_string(),
+            status: RunStatus::Failed,
+            pane_id: "1".to_string(),
+            pid: 1,
+            started_at: 0,
+            duration_ms: Some(12_000),
+            exit_code: Some(101),
+        }];
+
+        let items = picker_items(&config, &runs);
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].name, "build");
+        assert_eq!(items[0].preview[3], "last run  never");
+        assert_eq!(
+            items[1].preview,
+            [
+                "script    bash <script> users",
+                "cwd       (pane directory)",
+                "last run  never",
+            ]
+        );
+        assert_eq!(
+            items[2].preview,
+            [
+                "program   cargo",
+                "args      test --all",
+                "cwd       crates/core",
+                "last run  ✘ exit 101 after 12s",
+            ]
+        );
+    }
+}
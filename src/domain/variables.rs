@@ -0,0 +1,308 @@
+//! `${name}` variables in task definitions, expanded between loading the
+//! config and resolving the task to run
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::constants::DOTDIR;
+use crate::domain::models::{Command, ConfigError, ProjectConfig, Step};
+use crate::ports::EnvironmentPort;
+
+/// Built-in variables, besides `env:NAME` and the `[vars]` table
+const BUILTINS: [&str; 4] = ["project_root", "config_dir", "git_branch", "date"];
+
+pub struct VariableService<E: EnvironmentPort> {
+    environment: E,
+}
+
+impl<E: EnvironmentPort> VariableService<E> {
+    pub fn new(environment: E) -> Self {
+        Self { environment }
+    }
+
+    /// Expand the variables in the `program`, `args`, `steps`, `cwd` and `env` of
+    /// `task_name`. Other tasks are left alone, so a variable one of them
+    /// can't resolve doesn't stop the rest from running.
+    pub fn expand(&self, mut config: ProjectConfig, task_name: &str) -> Result<ProjectConfig> {
+        let Some(command) = config.tasks.get(task_name) else {
+            return Ok(config);
+        };
+
+        let expander = Expander {
+            environment: &self.environment,
+            config: &config,
+            task_name,
+        };
+        let expanded = expander.command(command)?;
+        config.tasks.insert(task_name.to_string(), expanded);
+        Ok(config)
+    }
+}
+
+struct Expander<'a, E: EnvironmentPort> {
+    environment: &'a E,
+    config: &'a ProjectConfig,
+    task_name: &'a str,
+}
+
+impl<E: EnvironmentPort> Expander<'_, E> {
+    fn command(&self, command: &Command) -> Result<Command, ConfigError> {
+        Ok(Command {
+            program: self.text(&command.program, &mut vec![])?,
+            args: command
+                .args
+                .iter()
+                .map(|arg| self.text(arg, &mut vec![]))
+                .collect::<Result<_, _>>()?,
+            steps: command
+                .steps
+                .iter()
+                .map(|step| {
+                    Ok(Step {
+                        program: self.text(&step.program, &mut vec![])?,
+                        args: step
+                            .args
+                            .iter()
+                            .map(|arg| self.text(arg, &mut vec![]))
+                            .collect::<Result<_, _>>()?,
+                        ..step.clone()
+                    })
+                })
+                .collect::<Result<_, ConfigError>>()?,
+            cwd: command
+                .cwd
+                .as_ref()
+                .map(|cwd| self.text(cwd, &mut vec![]))
+                .transpose()?,
+            env: command
+                .env
+                .iter()
+                .map(|(name, value)| Ok((name.clone(), self.text(value, &mut vec![])?)))
+                .collect::<Result<BTreeMap<_, _>, ConfigError>>()?,
+            ..command.clone()
+        })
+    }
+
+    /// Expand every `${name}` in `text`, `$${` stands for a literal `${`.
+    /// `resolving` holds the `[vars]` being expanded, to catch cycles.
+    fn text(&self, text: &str, resolving: &mut Vec<String>) -> Result<String, ConfigError> {
+        let mut expanded = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find("${") {
+            if rest[..start].ends_with('$') {
+                expanded.push_str(&rest[..start - 1]);
+                expanded.push_str("${");
+                rest = &rest[start + 2..];
+                continue;
+            }
+            expanded.push_str(&rest[..start]);
+
+            let Some(end) = rest[start..].find('}') else {
+                return Err(self.error(format!("unterminated `${{` in '{}'", text)));
+            };
+            let name = &rest[start + 2..start + end];
+            expanded.push_str(&self.value(name, resolving)?);
+            rest = &rest[start + end + 1..];
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+
+    fn value(&self, name: &str, resolving: &mut Vec<String>) -> Result<String, ConfigError> {
+        if let Some(var) = name.strip_prefix("env:") {
+            return self
+                .environment
+                .var(var)
+                .ok_or_else(|| self.error(format!("environment variable {} is not set", var)));
+        }
+
+        let root = match self.config.root.as_str() {
+            "" => ".",
+            root => root,
+        };
+        match name {
+            "project_root" => return Ok(root.to_string()),
+            "config_dir" => return Ok(Path::new(root).join(DOTDIR).to_string_lossy().to_string()),
+            "git_branch" => {
+                return self
+                    .environment
+                    .git_branch(root)
+                    .map_err(|err| self.error(format!("${{git_branch}}: {:#}", err)))
+            }
+            "date" => {
+                return self
+                    .environment
+                    .date()
+                    .map_err(|err| self.error(format!("${{date}}: {:#}", err)))
+            }
+            _ => {}
+        }
+
+        let Some(value) = self.config.vars.get(name) else {
+            return Err(self.error(format!(
+                "unknown variable ${{{}}}, define it in [vars] or use env:NAME, {}",
+                name,
+                BUILTINS.join(", ")
+            )));
+        };
+        if resolving.iter().any(|var| var == name) {
+            resolving.push(name.to_string());
+            return Err(self.error(format!(
+                "[vars] refer to themselves: {}",
+                resolving.join(" -> ")
+            )));
+        }
+
+        resolving.push(name.to_string());
+        let value = self.text(value, resolving)?;
+        resolving.pop();
+        Ok(value)
+    }
+
+    fn error(&self, message: String) -> ConfigError {
+        ConfigError::Variable(format!("task '{}': {}", self.task_name, message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::mock::MockEnvironmentPort;
+    use anyhow::anyhow;
+
+    fn config(command: Command) -> ProjectConfig {
+        let mut config = ProjectConfig {
+            root: "/work/app".to_string(),
+            ..Default::default()
+        };
+        config.tasks.insert("deploy".to_string(), command);
+        config
+    }
+
+    fn expand(config: ProjectConfig, environment: MockEnvironmentPort) -> Result<Command> {
+        let config = VariableService::new(environment).expand(config, "deploy")?;
+        Ok(config.tasks["deploy"].clone())
+    }
+
+    #[test]
+    fn test_expands_builtins_and_vars() {
+        let mut environment = MockEnvironmentPort::new();
+        environment
+            .expect_var()
+            .withf(|name| name == "HOME")
+            .returning(|_| Some("/home/me".to_string()));
+        environment
+            .expect_git_branch()
+            .withf(|dir| dir == "/work/app")
+            .returning(|_| Ok("main".to_string()));
+        environment
+            .expect_date()
+            .returning(|| Ok("2024-05-01".to_string()));
+
+        let mut config = config(Command {
+            program: "${config_dir}/deploy.sh".to_string(),
+            args: vec![
+                "--tag=${image}".to_string(),
+                "${env:HOME}".to_string(),
+                "$${literal}".to_string(),
+            ],
+            cwd: Some("${project_root}/ops".to_string()),
+            env: BTreeMap::from([("STAMP".to_string(), "${date}".to_string())]),
+            ..Default::default()
+        });
+        config.vars = BTreeMap::from([
+            ("image".to_string(), "${name}:${git_branch}".to_string()),
+            ("name".to_string(), "app".to_string()),
+        ]);
+
+        let command = expand(config, environment).unwrap();
+        assert_eq!(command.program, "/work/app/.wez/deploy.sh");
+        assert_eq!(command.args, ["--tag=app:main", "/home/me", "${literal}"]);
+        assert_eq!(command.cwd.as_deref(), Some("/work/app/ops"));
+        assert_eq!(command.env["STAMP"], "2024-05-01");
+    }
+
+    #[test]
+    fn test_only_expands_the_task_to_run() {
+        let mut config = config(Command {
+            program: "make".to_string(),
+            ..Default::default()
+        });
+        config.tasks.insert(
+            "other".to_string(),
+            Command {
+                program: "${missing}".to_string(),
+                ..Default::default()
+            },
+        );
+
+        let config = VariableService::new(MockEnvironmentPort::new())
+            .expand(config, "deploy")
+            .unwrap();
+        assert_eq!(config.tasks["other"].program, "${missing}");
+    }
+
+    #[test]
+    fn test_unknown_and_unset_variables_are_errors() {
+        let err = expand(
+            config(Command {
+                program: "${nope}".to_string(),
+                ..Default::default()
+            }),
+            MockEnvironmentPort::new(),
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("task 'deploy': unknown variable ${nope}"));
+
+        let mut environment = MockEnvironmentPort::new();
+        environment.expect_var().returning(|_| None);
+        let err = expand(
+            config(Command {
+                args: vec!["${env:TOKEN}".to_string()],
+                ..Default::default()
+            }),
+            environment,
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("environment variable TOKEN is not set"));
+
+        let mut environment = MockEnvironmentPort::new();
+        environment
+            .expect_git_branch()
+            .returning(|_| Err(anyhow!("not a repository")));
+        let err = expand(
+            config(Command {
+                args: vec!["${git_branch}".to_string()],
+                ..Default::default()
+            }),
+            environment,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("not a repository"));
+    }
+
+    #[test]
+    fn test_cycles_in_vars_are_errors() {
+        let mut config = config(Command {
+            program: "${a}".to_string(),
+            ..Default::default()
+        });
+        config.vars = BTreeMap::from([
+            ("a".to_string(), "${b}".to_string()),
+            ("b".to_string(), "x${a}".to_string()),
+        ]);
+
+        let err = expand(config, MockEnvironmentPort::new()).unwrap_err();
+        assert!(err.to_string().contains("a -> b -> a"), "{}", err);
+        assert_eq!(
+            crate::domain::models::FailureKind::from_error(&err),
+            crate::domain::models::FailureKind::Configuration
+        );
+    }
+}
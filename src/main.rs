@@ -1,4 +1,5 @@
-use std::{os::unix::process::ExitStatusExt, process::ExitStatus};
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitCode;
 
 use log::{error, info};
 use pretty_env_logger::init_timed;
@@ -10,13 +11,22 @@ mod domain;
 mod ports;
 
 use application::Application;
+use domain::models::FailureKind;
 
-fn main() {
+fn main() -> ExitCode {
     init_timed();
     info!("Wez Bits");
 
-    if let Err(err) = Application::run() {
-        error!("Wez Bits Failed: {:#}", &err);
-        ExitStatus::from_raw(1);
+    match Application::run() {
+        // Propagate the task's own exit code, or 128 + signal when it was killed
+        Ok(status) => match (status.code(), status.signal()) {
+            (Some(code), _) => ExitCode::from(code as u8),
+            (None, Some(signal)) => ExitCode::from(128 + signal as u8),
+            (None, None) => ExitCode::FAILURE,
+        },
+        Err(err) => {
+            error!("Wez Bits Failed: {:#}", &err);
+            ExitCode::from(FailureKind::from_error(&err).exit_code())
+        }
     }
 }
@@ -1,15 +1,21 @@
+use crate::domain::models::{ConfigFormat, ProjectConfig};
 use anyhow::Result;
-use crate::domain::models::TaskConfig;
 
 /// Port for configuration management
 #[cfg_attr(test, mockall::automock)]
 pub trait ConfigPort {
-    /// Load task configuration
-    fn load_config(&self) -> Result<TaskConfig>;
-    
+    /// Load project configuration (settings and tasks)
+    fn load_config(&self) -> Result<ProjectConfig>;
+
     /// Create default configuration
     fn create_default_config(&self) -> Result<()>;
-    
-    /// View current configuration
-    fn view_config(&self) -> Result<String>;
-}
\ No newline at end of file
+
+    /// Render the current configuration in the given format
+    fn view_config(&self, format: ConfigFormat) -> Result<String>;
+
+    /// Path of the configuration file
+    fn config_path(&self) -> String;
+
+    /// Directory the project's tasks run from
+    fn project_root(&self) -> String;
+}
@@ -0,0 +1,23 @@
+use anyhow::Result;
+
+/// Port for probing the environment wez bits runs in
+#[cfg_attr(test, mockall::automock)]
+pub trait DiagnosticsPort {
+    /// Version reported by the wezterm binary
+    fn wezterm_version(&self) -> Result<String>;
+
+    /// Number of panes the wezterm mux reports
+    fn list_panes(&self) -> Result<usize>;
+
+    /// Pane id of the WezTerm pane wez bits runs in
+    fn current_pane(&self) -> Option<String>;
+
+    /// Path of the project configuration file, if one exists
+    fn config_path(&self) -> Option<String>;
+
+    /// Locate an executable on PATH
+    fn find_executable(&self, program: &str) -> Option<String>;
+
+    /// Ensure the state directory exists and is writable, returning its path
+    fn check_state_dir(&self) -> Result<String>;
+}
@@ -0,0 +1,14 @@
+use anyhow::Result;
+
+/// Port for the values behind built-in config variables
+#[cfg_attr(test, mockall::automock)]
+pub trait EnvironmentPort {
+    /// Environment variable of the `wzb` process
+    fn var(&self, name: &str) -> Option<String>;
+
+    /// Branch checked out in the git repository holding `dir`
+    fn git_branch(&self, dir: &str) -> Result<String>;
+
+    /// Today's local date as `YYYY-MM-DD`
+    fn date(&self) -> Result<String>;
+}
@@ -11,4 +11,7 @@ pub trait FileSystemPort {
 
     /// Read content from a file
     fn read_from_file(&self, path: &str) -> Result<String>;
+
+    /// Locate an executable on PATH
+    fn find_executable(&self, program: &str) -> Option<String>;
 }
@@ -1,16 +1,28 @@
 pub mod config_port;
+pub mod diagnostics_port;
+pub mod environment_port;
 pub mod filesystem_port;
+pub mod prompt_port;
+pub mod state_port;
 pub mod terminal_port;
 
 // Re-export ports
 pub use config_port::ConfigPort;
+pub use diagnostics_port::DiagnosticsPort;
+pub use environment_port::EnvironmentPort;
 pub use filesystem_port::FileSystemPort;
+pub use prompt_port::PromptPort;
+pub use state_port::StatePort;
 pub use terminal_port::TerminalPort;
 
 // Re-export mock implementations for testing
 #[cfg(test)]
 pub mod mock {
     pub use super::config_port::MockConfigPort;
+    pub use super::diagnostics_port::MockDiagnosticsPort;
+    pub use super::environment_port::MockEnvironmentPort;
     pub use super::filesystem_port::MockFileSystemPort;
+    pub use super::prompt_port::MockPromptPort;
+    pub use super::state_port::MockStatePort;
     pub use super::terminal_port::MockTerminalPort;
 }
@@ -0,0 +1,25 @@
+use anyhow::Result;
+
+use crate::domain::models::Key;
+
+/// Port for interactive input on the controlling terminal
+#[cfg_attr(test, mockall::automock)]
+pub trait PromptPort {
+    /// Whether stdin is a terminal someone can answer on
+    fn is_interactive(&self) -> bool;
+
+    /// Take over the terminal to read single key presses
+    fn start(&self) -> Result<()>;
+
+    /// Block until the next key press
+    fn read_key(&self) -> Result<Key>;
+
+    /// Replace the screen contents with these lines
+    fn draw(&self, lines: &[String]) -> Result<()>;
+
+    /// Visible rows and columns
+    fn size(&self) -> (usize, usize);
+
+    /// Give the terminal back in the state `start` found it
+    fn finish(&self) -> Result<()>;
+}
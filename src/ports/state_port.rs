@@ -0,0 +1,19 @@
+use anyhow::Result;
+
+use crate::domain::models::{HistoryEntry, TaskRun};
+
+/// Port for persisting task state between invocations
+#[cfg_attr(test, mockall::automock)]
+pub trait StatePort: Send + Sync {
+    /// Store the latest state of a task run, replacing the previous one
+    fn record_run(&self, run: &TaskRun) -> Result<()>;
+
+    /// Latest state of every task of the project
+    fn load_runs(&self) -> Result<Vec<TaskRun>>;
+
+    /// Add a finished run to the end of the project's history
+    fn append_history(&self, entry: &HistoryEntry) -> Result<()>;
+
+    /// Every recorded run of the project, oldest first
+    fn load_history(&self) -> Result<Vec<HistoryEntry>>;
+}
@@ -1,17 +1,67 @@
 use anyhow::Result;
 use std::process::ExitStatus;
 
-use crate::domain::models::Direction;
+use crate::domain::models::{Direction, Invocation, Signal};
 
-/// Port for terminal operations
+/// Port for terminal operations, shared by tasks running in parallel
 #[cfg_attr(test, mockall::automock)]
-pub trait TerminalPort {
+pub trait TerminalPort: Send + Sync {
     /// Open a new pane in the terminal
     fn open_pane(&self, direction: Direction, size: i32) -> Result<String>;
 
     /// Close a pane
     fn close_pane(&self, pane_id: &str) -> Result<()>;
 
-    /// Pipe text to a pane
-    fn pipe_text_to_pane(&self, args: Vec<String>, pane_id: &str) -> Result<ExitStatus>;
+    /// Run a program in a pane and wait for its exit status
+    fn pipe_text_to_pane(&self, invocation: Invocation, pane_id: &str) -> Result<ExitStatus>;
+
+    /// Signal the task running in a pane, started from an invocation with a timeout
+    fn signal_task(&self, pane_id: &str, signal: Signal) -> Result<()>;
+
+    /// Set the title of a pane
+    fn set_pane_title(&self, pane_id: &str, title: &str) -> Result<()>;
+
+    /// Set the title of the tab holding a pane, an empty title restores the default
+    fn set_tab_title(&self, pane_id: &str, title: &str) -> Result<()>;
+
+    /// Show a desktop notification originating from a pane
+    fn notify(&self, pane_id: &str, title: &str, body: &str) -> Result<()>;
+
+    /// Publish a user variable on a pane, readable from `wezterm.lua`
+    fn set_user_var(&self, pane_id: &str, name: &str, value: &str) -> Result<()>;
+}
+
+/// Allows selecting a terminal backend at runtime
+impl<T: TerminalPort + ?Sized> TerminalPort for Box<T> {
+    fn open_pane(&self, direction: Direction, size: i32) -> Result<String> {
+        (**self).open_pane(direction, size)
+    }
+
+    fn close_pane(&self, pane_id: &str) -> Result<()> {
+        (**self).close_pane(pane_id)
+    }
+
+    fn pipe_text_to_pane(&self, invocation: Invocation, pane_id: &str) -> Result<ExitStatus> {
+        (**self).pipe_text_to_pane(invocation, pane_id)
+    }
+
+    fn signal_task(&self, pane_id: &str, signal: Signal) -> Result<()> {
+        (**self).signal_task(pane_id, signal)
+    }
+
+    fn set_pane_title(&self, pane_id: &str, title: &str) -> Result<()> {
+        (**self).set_pane_title(pane_id, title)
+    }
+
+    fn set_tab_title(&self, pane_id: &str, title: &str) -> Result<()> {
+        (**self).set_tab_title(pane_id, title)
+    }
+
+    fn notify(&self, pane_id: &str, title: &str, body: &str) -> Result<()> {
+        (**self).notify(pane_id, title, body)
+    }
+
+    fn set_user_var(&self, pane_id: &str, name: &str, value: &str) -> Result<()> {
+        (**self).set_user_var(pane_id, name, value)
+    }
 }